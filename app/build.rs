@@ -4,12 +4,13 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use clap::CommandFactory;
 use clap_complete::{generate_to, shells};
 use std::env;
 use std::io::Error;
 use std::path::{Path, PathBuf};
 
-include!("../lib/src/args.rs");
+include!("src/args.rs");
 
 fn get_output_path() -> PathBuf {
     let out_dir = env::var("OUT_DIR").unwrap();
@@ -4,7 +4,6 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-pub use clap::CommandFactory;
 pub use clap::Parser;
 
 static LONG_ABOUT: &str = "
@@ -30,6 +29,22 @@ pub struct Arguments {
     #[arg(global = true, long = "sparse-add")]
     pub sparse_add: bool,
 
+    /// Formats files with syntax errors, copying the broken statements through unchanged.
+    #[arg(global = true, long = "tolerant")]
+    pub tolerant: bool,
+
+    /// Wraps statements longer than this many columns with ... continuations.
+    #[arg(global = true, long = "max-width")]
+    pub max_width: Option<usize>,
+
+    /// Checks that the formatted code means the same as the input before writing it.
+    #[arg(global = true, long = "verify")]
+    pub verify: bool,
+
+    /// Formats the output a second time and fails with a diff if that changes anything.
+    #[arg(global = true, long = "ensure-idempotent")]
+    pub ensure_idempotent: bool,
+
     /// Whether files should be formatted inplace instead of printing to stdout.
     #[arg(global = true, long = "inplace")]
     pub inplace: bool,
@@ -0,0 +1,53 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Measures how aligning the columns of multi-line matrices scales with the
+//! number of rows and with nesting. Both should grow about linearly.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use matlab_beautifier::Formatter;
+use std::hint::black_box;
+
+/// A matrix of `rows` rows of mixed cells.
+fn rows(rows: usize) -> String {
+    let rows: Vec<String> = (0..rows)
+        .map(|i| format!("{}, -{}, foo(a,{}), 'abc'", i, i * 7, i % 13))
+        .collect();
+    format!("x = [{}];\n", rows.join("\n"))
+}
+
+/// A matrix nested `depth` times in the first cell of multi-line matrices.
+fn nested(depth: usize) -> String {
+    let mut code = "1".to_string();
+    for i in 0..depth {
+        code = format!("[{}, -{}\n{}, 3]", code, i, i * 11);
+    }
+    format!("x = {};\n", code)
+}
+
+fn bench(c: &mut Criterion, name: &str, sizes: &[usize], code: fn(usize) -> String) {
+    let formatter = Formatter::default();
+    let mut group = c.benchmark_group(name);
+    for &size in sizes {
+        let code = code(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &code, |b, code| {
+            b.iter(|| formatter.format_str(black_box(code)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn matrix_rows(c: &mut Criterion) {
+    bench(c, "matrix_rows", &[100, 1_000, 10_000], rows);
+}
+
+fn matrix_nesting(c: &mut Criterion) {
+    bench(c, "matrix_nesting", &[4, 16, 64], nested);
+}
+
+criterion_group!(benches, matrix_rows, matrix_nesting);
+criterion_main!(benches);
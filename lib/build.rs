@@ -0,0 +1,30 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::env;
+use std::path::Path;
+
+/// Generates the C header for the functions in `src/ffi.rs` into `OUT_DIR`,
+/// where `tests/ffi.rs` compares it to the one in `include`. Set
+/// `MATLAB_BEAUTIFIER_UPDATE_HEADER` to update that one too.
+fn main() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=MATLAB_BEAUTIFIER_UPDATE_HEADER");
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let crate_dir = Path::new(&crate_dir);
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+    let bindings = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/ffi.rs"))
+        .generate()
+        .expect("cannot generate the C header");
+    bindings.write_to_file(Path::new(&out_dir).join("matlab_beautifier.h"));
+    if env::var_os("MATLAB_BEAUTIFIER_UPDATE_HEADER").is_some() {
+        bindings.write_to_file(crate_dir.join("include/matlab_beautifier.h"));
+    }
+}
@@ -0,0 +1,51 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// The checks of the `format` fuzz target, included by it and by
+// `tests/fuzz_corpus.rs`, which replays the corpus.
+
+use matlab_beautifier::{ast, FormatError, FormatOptions, Formatter};
+
+/// Formats `data` and checks that the formatter does not panic or fail with a
+/// bug on code that parses, and that whatever it formats parses again and
+/// stays the same when formatted once more. The first byte of `data` picks
+/// the options, the rest is the code.
+pub fn check(data: &[u8]) {
+    let Some((&flags, code)) = data.split_first() else {
+        return;
+    };
+    let Ok(code) = std::str::from_utf8(code) else {
+        return;
+    };
+    let options = FormatOptions::new()
+        .with_sparse_math(flags & 1 != 0)
+        .with_sparse_add(flags & 2 != 0)
+        .with_tolerant(flags & 4 != 0)
+        .with_max_width((flags & 8 != 0).then_some(40));
+    let formatter = Formatter::new(options);
+    let parses = !ast::parse(code).unwrap().root_node().has_error();
+    let formatted = match formatter.format_str(code) {
+        Ok(formatted) => formatted,
+        Err(err) => {
+            assert!(
+                !parses || !matches!(err, FormatError::Internal { .. }),
+                "formatting failed: {}",
+                err
+            );
+            return;
+        }
+    };
+    if parses {
+        let output = ast::parse(&formatted).unwrap();
+        assert!(
+            !output.root_node().has_error(),
+            "output does not parse:\n{}",
+            formatted
+        );
+    }
+    let again = formatter.format_str(&formatted).unwrap();
+    assert_eq!(again, formatted, "formatting is not idempotent");
+}
@@ -0,0 +1,115 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+#ifndef MATLAB_BEAUTIFIER_H
+#define MATLAB_BEAUTIFIER_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The outcome of a call to `mb_format`.
+typedef enum mb_status {
+  MB_STATUS_OK = 0,
+  // A pointer that must not be NULL was NULL.
+  MB_STATUS_INVALID_ARGUMENT,
+  // The code is not valid UTF-8.
+  MB_STATUS_INVALID_UTF8,
+  // The code does not parse.
+  MB_STATUS_SYNTAX_ERROR,
+  // Formatting failed for another reason, see `mb_error_message`.
+  MB_STATUS_ERROR,
+  // The formatter panicked. This is a bug in the formatter.
+  MB_STATUS_PANIC,
+} mb_status;
+
+// Why formatting failed, read through the `mb_error_*` functions.
+typedef struct mb_error mb_error;
+
+// Options controlling how code is formatted, see `FormatOptions`.
+typedef struct mb_options {
+  bool sparse_math;
+  bool sparse_add;
+  bool tolerant;
+  // The column statements are wrapped at, 0 to never wrap them.
+  size_t max_width;
+  bool verify;
+  bool ensure_idempotent;
+} mb_options;
+
+// The output of `mb_format`, to be released with `mb_result_free`.
+typedef struct mb_result {
+  // The formatted code, NUL-terminated, or NULL if formatting failed.
+  char *text;
+  // The length of `text` in bytes, without the NUL.
+  size_t len;
+  // Why formatting failed, or NULL if it succeeded.
+  struct mb_error *error;
+} mb_result;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The default options: dense math operators everywhere, no wrapping.
+struct mb_options mb_options_default(void);
+
+// Formats the `len` bytes of UTF-8 code at `code` into `result`.
+//
+// `options` may be NULL to use the defaults. `result` is overwritten without
+// being released first and must be released with `mb_result_free`, also
+// when formatting failed.
+//
+// # Safety
+//
+// `code` must point to `len` readable bytes, `options` must be NULL or point
+// to valid options and `result` must point to writable memory for an
+// `mb_result`.
+enum mb_status mb_format(const char *code,
+                         size_t len,
+                         const struct mb_options *options,
+                         struct mb_result *result);
+
+// Releases what `mb_format` stored in `result` and resets it. Does nothing
+// if `result` is NULL.
+//
+// # Safety
+//
+// `result` must be NULL or have been filled in by `mb_format`, and not be
+// released already.
+void mb_result_free(struct mb_result *result);
+
+// The message describing why formatting failed, or NULL if it succeeded.
+// The message lives as long as `result` is not released.
+//
+// # Safety
+//
+// `result` must be NULL or have been filled in by `mb_format`.
+const char *mb_error_message(const struct mb_result *result);
+
+// The 1-based line where formatting failed, or 0 if unknown.
+//
+// # Safety
+//
+// `result` must be NULL or have been filled in by `mb_format`.
+size_t mb_error_line(const struct mb_result *result);
+
+// The 1-based column, in bytes, where formatting failed, or 0 if unknown.
+//
+// # Safety
+//
+// `result` must be NULL or have been filled in by `mb_format`.
+size_t mb_error_column(const struct mb_result *result);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MATLAB_BEAUTIFIER_H */
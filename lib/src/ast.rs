@@ -0,0 +1,820 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! A typed view of the tree-sitter-matlab syntax tree.
+//!
+//! Each type wraps a [`Node`] of a known kind and has accessors for its
+//! children. Required children that are missing, which only happens when the
+//! grammar changed, are reported as [`FormatError::Internal`] instead of
+//! silently skipping them. Expressions are left as plain nodes, except for the
+//! ones whose parts the formatter looks at.
+//!
+//! ```
+//! use matlab_beautifier::ast::{AstNode, IfStatement};
+//!
+//! let code = "if x\n    y = 1;\nelse\n    y = 2;\nend\n";
+//! let tree = matlab_beautifier::ast::parse(code)?;
+//! let node = tree.root_node().named_child(0).unwrap();
+//! let statement = IfStatement::cast(node).unwrap();
+//! assert_eq!(statement.condition()?.utf8_text(code.as_bytes()).unwrap(), "x");
+//! assert!(statement.else_clause().is_some());
+//! # Ok::<(), matlab_beautifier::FormatError>(())
+//! ```
+
+use super::error::{FormatError, Result};
+use super::formatter;
+use tree_sitter::Node;
+pub use tree_sitter::Tree;
+
+/// Parses `code` into a syntax tree to view through the types of this module.
+pub fn parse(code: &str) -> Result<Tree> {
+    formatter::parse(code)
+}
+
+/// A syntax node of a known kind.
+pub trait AstNode<'t>: Copy {
+    /// Whether nodes of `kind` can be viewed as this type.
+    fn can_cast(kind: &str) -> bool;
+
+    /// Views `node` as this type, if it is of the right kind.
+    fn cast(node: Node<'t>) -> Option<Self>;
+
+    /// The underlying syntax node.
+    fn syntax(&self) -> Node<'t>;
+}
+
+macro_rules! ast_node {
+    ($(#[$meta:meta])* $name:ident: $($kind:literal)|+) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $name<'t>(Node<'t>);
+
+        impl<'t> AstNode<'t> for $name<'t> {
+            fn can_cast(kind: &str) -> bool {
+                matches!(kind, $($kind)|+)
+            }
+
+            fn cast(node: Node<'t>) -> Option<Self> {
+                Self::can_cast(node.kind()).then_some(Self(node))
+            }
+
+            fn syntax(&self) -> Node<'t> {
+                self.0
+            }
+        }
+    };
+}
+
+/// Views `node` as `T`, failing if it is of another kind.
+pub(crate) fn cast<'t, T: AstNode<'t>>(node: Node<'t>) -> Result<T> {
+    T::cast(node).ok_or_else(|| FormatError::internal(&node, "unexpected node kind"))
+}
+
+/// The children of `node` that can be viewed as `T`.
+fn children<'t, T: AstNode<'t>>(node: Node<'t>) -> impl Iterator<Item = T> + 't {
+    (0..node.child_count()).filter_map(move |i| node.child(i).and_then(T::cast))
+}
+
+/// The first child of `node` that can be viewed as `T`.
+fn child<'t, T: AstNode<'t>>(node: Node<'t>) -> Option<T> {
+    children(node).next()
+}
+
+/// The child of `node` in `field`, which the grammar always fills in.
+fn field<'t>(node: Node<'t>, field: &str) -> Result<Node<'t>> {
+    node.child_by_field_name(field)
+        .ok_or_else(|| missing(&node))
+}
+
+/// The error for a required child of `node` the grammar did not produce.
+fn missing(node: &Node) -> FormatError {
+    FormatError::internal(node, "expected child node is missing")
+}
+
+/// The named children of `node`, without line continuations.
+fn named_children<'t>(node: Node<'t>) -> impl Iterator<Item = Node<'t>> + 't {
+    (0..node.named_child_count())
+        .filter_map(move |i| node.named_child(i))
+        .filter(|c| !LineContinuation::can_cast(c.kind()))
+}
+
+/// The `get.` or `set.` prefix of a property accessor's name.
+fn accessor_prefix<'t>(node: Node<'t>, code: &[u8]) -> Option<Node<'t>> {
+    (0..node.child_count())
+        .filter_map(|i| node.child(i))
+        .filter(|c| !c.is_named())
+        .find(|c| matches!(c.utf8_text(code), Ok("get." | "set.")))
+}
+
+ast_node!(
+    /// A list of statements.
+    Block: "block"
+);
+
+impl<'t> Block<'t> {
+    /// The statements and comments of the block.
+    ///
+    /// tree-sitter leaves the comments between the header of a compound
+    /// statement and its first statement, as well as those after the last
+    /// one, outside of the block. They are included here, except for linter
+    /// directives on the header line, which belong to the statement itself.
+    pub fn items(&self, code: &[u8]) -> Vec<Node<'t>> {
+        block_items(self.0, code)
+    }
+}
+
+/// The statements and comments of `node`, a block or the root of a file, see
+/// [`Block::items`].
+pub(crate) fn block_items<'t>(node: Node<'t>, code: &[u8]) -> Vec<Node<'t>> {
+    let mut items: Vec<Node> = vec![];
+    let mut prev_node = node;
+    while let Some(n) = prev_node.prev_named_sibling() {
+        prev_node = n;
+        match Comment::cast(n) {
+            Some(comment) if comment.is_directive(code) => continue,
+            Some(_) => items.insert(0, n),
+            None => break,
+        }
+    }
+    let mut cursor = node.walk();
+    items.extend(node.named_children(&mut cursor));
+    items.extend(comments_after(node).map(|c| c.syntax()));
+    items
+}
+
+ast_node!(
+    /// A comment, either a line or a `%{ %}` block.
+    Comment: "comment"
+);
+
+impl Comment<'_> {
+    /// Whether this is a directive for the code analyzer like `%#ok`, which
+    /// stays on the line of the statement it is about.
+    pub fn is_directive(&self, code: &[u8]) -> bool {
+        matches!(self.0.utf8_text(code), Ok(text) if text.starts_with("%#"))
+    }
+}
+
+/// The linter directives among the children of `node`, like the `%#ok` after
+/// `if x`.
+pub(crate) fn directives<'t>(node: Node<'t>, code: &'t [u8]) -> impl Iterator<Item = Comment<'t>> {
+    children::<Comment>(node).filter(|c| c.is_directive(code))
+}
+
+/// The other comments among the children of `node`, which is where the
+/// comments of a clause without statements end up.
+pub(crate) fn non_directive_comments<'t>(
+    node: Node<'t>,
+    code: &'t [u8],
+) -> impl Iterator<Item = Comment<'t>> {
+    children::<Comment>(node).filter(|c| !c.is_directive(code))
+}
+
+/// The comments right after `node`, up to the next statement or clause.
+pub(crate) fn comments_after(node: Node) -> impl Iterator<Item = Comment> {
+    std::iter::successors(node.next_named_sibling(), Node::next_named_sibling)
+        .map_while(Comment::cast)
+}
+
+ast_node!(
+    /// A `...` continuing a statement on the next line, with the comment after
+    /// it.
+    LineContinuation: "line_continuation"
+);
+
+ast_node!(IfStatement: "if_statement");
+
+impl<'t> IfStatement<'t> {
+    pub fn condition(&self) -> Result<Node<'t>> {
+        field(self.0, "condition")
+    }
+
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+
+    pub fn elseif_clauses(&self) -> impl Iterator<Item = ElseIfClause<'t>> + 't {
+        children(self.0)
+    }
+
+    pub fn else_clause(&self) -> Option<ElseClause<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(ElseIfClause: "elseif_clause");
+
+impl<'t> ElseIfClause<'t> {
+    pub fn condition(&self) -> Result<Node<'t>> {
+        field(self.0, "condition")
+    }
+
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(ElseClause: "else_clause");
+
+impl<'t> ElseClause<'t> {
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(WhileStatement: "while_statement");
+
+impl<'t> WhileStatement<'t> {
+    pub fn condition(&self) -> Result<Node<'t>> {
+        field(self.0, "condition")
+    }
+
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(
+    /// A `for` or `parfor` loop.
+    ForStatement: "for_statement"
+);
+
+impl<'t> ForStatement<'t> {
+    /// The `for` or `parfor` keyword.
+    pub fn keyword(&self) -> Result<Node<'t>> {
+        self.0.child(0).ok_or_else(|| missing(&self.0))
+    }
+
+    pub fn iterator(&self) -> Result<LoopIterator<'t>> {
+        child(self.0).ok_or_else(|| missing(&self.0))
+    }
+
+    /// The options of a `parfor` loop, e.g. the maximum number of workers.
+    pub fn parfor_options(&self) -> Option<ParforOptions<'t>> {
+        child(self.0)
+    }
+
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(
+    /// The `i = values` part of a loop.
+    LoopIterator: "iterator"
+);
+
+impl<'t> LoopIterator<'t> {
+    pub fn variable(&self) -> Result<Node<'t>> {
+        named_children(self.0)
+            .next()
+            .ok_or_else(|| missing(&self.0))
+    }
+
+    pub fn values(&self) -> Result<Node<'t>> {
+        named_children(self.0)
+            .nth(1)
+            .ok_or_else(|| missing(&self.0))
+    }
+}
+
+ast_node!(ParforOptions: "parfor_options");
+
+impl<'t> ParforOptions<'t> {
+    pub fn value(&self) -> Result<Node<'t>> {
+        named_children(self.0)
+            .next()
+            .ok_or_else(|| missing(&self.0))
+    }
+}
+
+ast_node!(SwitchStatement: "switch_statement");
+
+impl<'t> SwitchStatement<'t> {
+    pub fn condition(&self) -> Result<Node<'t>> {
+        field(self.0, "condition")
+    }
+
+    pub fn cases(&self) -> impl Iterator<Item = CaseClause<'t>> + 't {
+        children(self.0)
+    }
+
+    pub fn otherwise(&self) -> Option<OtherwiseClause<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(CaseClause: "case_clause");
+
+impl<'t> CaseClause<'t> {
+    pub fn condition(&self) -> Result<Node<'t>> {
+        field(self.0, "condition")
+    }
+
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(OtherwiseClause: "otherwise_clause");
+
+impl<'t> OtherwiseClause<'t> {
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(TryStatement: "try_statement");
+
+impl<'t> TryStatement<'t> {
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+
+    pub fn catch_clause(&self) -> Option<CatchClause<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(CatchClause: "catch_clause");
+
+impl<'t> CatchClause<'t> {
+    /// The variable the exception is caught into, if any.
+    pub fn identifier(&self) -> Option<Node<'t>> {
+        named_children(self.0).find(|c| c.kind() == "identifier")
+    }
+
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(SpmdStatement: "spmd_statement");
+
+impl<'t> SpmdStatement<'t> {
+    /// The expressions in parentheses after `spmd`, if any.
+    pub fn workers(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        named_children(self.0).filter(|c| c.kind() != "block")
+    }
+
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(FunctionDefinition: "function_definition");
+
+impl<'t> FunctionDefinition<'t> {
+    pub fn output(&self) -> Option<FunctionOutput<'t>> {
+        child(self.0)
+    }
+
+    /// The `get.` or `set.` prefix of a property accessor method.
+    pub fn accessor_prefix(&self, code: &[u8]) -> Option<Node<'t>> {
+        accessor_prefix(self.0, code)
+    }
+
+    pub fn name(&self) -> Result<Node<'t>> {
+        field(self.0, "name")
+    }
+
+    pub fn arguments(&self) -> Option<FunctionArguments<'t>> {
+        child(self.0)
+    }
+
+    pub fn arguments_statements(&self) -> impl Iterator<Item = ArgumentsStatement<'t>> + 't {
+        children(self.0)
+    }
+
+    pub fn block(&self) -> Option<Block<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(
+    /// A method declared in a `methods` block without a body.
+    FunctionSignature: "function_signature"
+);
+
+impl<'t> FunctionSignature<'t> {
+    pub fn output(&self) -> Option<FunctionOutput<'t>> {
+        child(self.0)
+    }
+
+    /// The `get.` or `set.` prefix of a property accessor method.
+    pub fn accessor_prefix(&self, code: &[u8]) -> Option<Node<'t>> {
+        accessor_prefix(self.0, code)
+    }
+
+    pub fn name(&self) -> Result<Node<'t>> {
+        field(self.0, "name")
+    }
+
+    pub fn arguments(&self) -> Option<FunctionArguments<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(
+    /// The `out = ` or `[a, b] = ` part of a function definition.
+    FunctionOutput: "function_output"
+);
+
+impl<'t> FunctionOutput<'t> {
+    pub fn value(&self) -> Result<Node<'t>> {
+        self.0.child(0).ok_or_else(|| missing(&self.0))
+    }
+}
+
+ast_node!(FunctionArguments: "function_arguments");
+
+impl<'t> FunctionArguments<'t> {
+    pub fn arguments(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        named_children(self.0)
+    }
+}
+
+ast_node!(
+    /// An `arguments ... end` validation block.
+    ArgumentsStatement: "arguments_statement"
+);
+
+impl<'t> ArgumentsStatement<'t> {
+    /// The `(Input)`, `(Output)` or `(Repeating)` after `arguments`.
+    pub fn attributes(&self) -> Option<Attributes<'t>> {
+        child(self.0)
+    }
+
+    pub fn properties(&self) -> impl Iterator<Item = Property<'t>> + 't {
+        children(self.0)
+    }
+}
+
+ast_node!(ClassDefinition: "class_definition");
+
+impl<'t> ClassDefinition<'t> {
+    pub fn attributes(&self) -> Option<Attributes<'t>> {
+        child(self.0)
+    }
+
+    pub fn name(&self) -> Result<Node<'t>> {
+        field(self.0, "name")
+    }
+
+    pub fn superclasses(&self) -> Option<Superclasses<'t>> {
+        child(self.0)
+    }
+
+    pub fn properties(&self) -> impl Iterator<Item = Properties<'t>> + 't {
+        children(self.0)
+    }
+
+    pub fn methods(&self) -> impl Iterator<Item = Methods<'t>> + 't {
+        children(self.0)
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = Events<'t>> + 't {
+        children(self.0)
+    }
+
+    pub fn enumerations(&self) -> impl Iterator<Item = Enumeration<'t>> + 't {
+        children(self.0)
+    }
+
+    /// The `properties`, `methods`, `events` and `enumeration` blocks, in the
+    /// order they are written in.
+    pub fn members(&self) -> impl Iterator<Item = ClassMember<'t>> + 't {
+        named_children(self.0).filter_map(ClassMember::cast)
+    }
+}
+
+/// A block in the body of a class definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClassMember<'t> {
+    Properties(Properties<'t>),
+    Methods(Methods<'t>),
+    Events(Events<'t>),
+    Enumeration(Enumeration<'t>),
+}
+
+impl<'t> ClassMember<'t> {
+    pub fn cast(node: Node<'t>) -> Option<Self> {
+        Properties::cast(node)
+            .map(Self::Properties)
+            .or_else(|| Methods::cast(node).map(Self::Methods))
+            .or_else(|| Events::cast(node).map(Self::Events))
+            .or_else(|| Enumeration::cast(node).map(Self::Enumeration))
+    }
+
+    pub fn syntax(&self) -> Node<'t> {
+        match self {
+            Self::Properties(properties) => properties.syntax(),
+            Self::Methods(methods) => methods.syntax(),
+            Self::Events(events) => events.syntax(),
+            Self::Enumeration(enumeration) => enumeration.syntax(),
+        }
+    }
+}
+
+ast_node!(Superclasses: "superclasses");
+
+impl<'t> Superclasses<'t> {
+    pub fn names(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        named_children(self.0)
+    }
+}
+
+ast_node!(
+    /// The `(Name = value, ...)` after `classdef`, `properties`, etc.
+    Attributes: "attributes"
+);
+
+impl<'t> Attributes<'t> {
+    pub fn attributes(&self) -> impl Iterator<Item = Attribute<'t>> + 't {
+        children(self.0)
+    }
+}
+
+ast_node!(Attribute: "attribute");
+
+impl<'t> Attribute<'t> {
+    pub fn name(&self) -> Result<Node<'t>> {
+        self.0.named_child(0).ok_or_else(|| missing(&self.0))
+    }
+
+    pub fn value(&self) -> Option<Node<'t>> {
+        self.0.named_child(1)
+    }
+}
+
+ast_node!(
+    /// A `properties ... end` block.
+    Properties: "properties"
+);
+
+impl<'t> Properties<'t> {
+    pub fn attributes(&self) -> Option<Attributes<'t>> {
+        child(self.0)
+    }
+
+    /// The `property` children. Only arguments blocks have `class_property`
+    /// ones too, see [`ArgumentsStatement::properties`].
+    pub fn properties(&self) -> impl Iterator<Item = Property<'t>> + 't {
+        children(self.0).filter(|p: &Property| p.0.kind() == "property")
+    }
+}
+
+ast_node!(
+    /// A property of a class or an argument in an `arguments` block.
+    Property: "property" | "class_property"
+);
+
+impl<'t> Property<'t> {
+    pub fn name(&self) -> Result<Node<'t>> {
+        field(self.0, "name")
+    }
+
+    /// Whether the class is given the old way, as in `name@class`.
+    pub fn is_old_style(&self) -> bool {
+        (0..self.0.child_count()).any(|i| self.0.child(i).is_some_and(|c| c.kind() == "@"))
+    }
+
+    pub fn dimensions(&self) -> Option<Dimensions<'t>> {
+        child(self.0)
+    }
+
+    /// The class the value is restricted to, if any. Given the old way, it is
+    /// always a plain identifier.
+    pub fn class(&self) -> Option<Node<'t>> {
+        let name = self.name().ok()?;
+        let old_style = self.is_old_style();
+        named_children(self.0)
+            .filter(|c| c.id() != name.id())
+            .find(|c| c.kind() == "identifier" || !old_style && c.kind() == "property_name")
+    }
+
+    /// The kind of the value given the old way, as in `name@class scalar`.
+    pub fn old_style_kind(&self) -> Option<Node<'t>> {
+        let name = self.name().ok()?;
+        let class = self.class()?;
+        named_children(self.0)
+            .filter(|c| c.id() != name.id() && c.id() != class.id())
+            .find(|c| c.kind() == "identifier")
+    }
+
+    pub fn validation_functions(&self) -> Option<Node<'t>> {
+        named_children(self.0).find(|c| c.kind() == "validation_functions")
+    }
+
+    /// The default value, without the `=`.
+    pub fn default_value(&self) -> Option<Node<'t>> {
+        named_children(self.0)
+            .find(|c| c.kind() == "default_value")
+            .and_then(|c| c.named_child(0))
+    }
+}
+
+ast_node!(
+    /// The `(m,n)` size restriction of a property.
+    Dimensions: "dimensions"
+);
+
+impl<'t> Dimensions<'t> {
+    pub fn dimensions(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        named_children(self.0)
+    }
+}
+
+ast_node!(
+    /// A `methods ... end` block.
+    Methods: "methods"
+);
+
+impl<'t> Methods<'t> {
+    pub fn attributes(&self) -> Option<Attributes<'t>> {
+        child(self.0)
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = FunctionDefinition<'t>> + 't {
+        children(self.0)
+    }
+
+    pub fn signatures(&self) -> impl Iterator<Item = FunctionSignature<'t>> + 't {
+        children(self.0)
+    }
+}
+
+ast_node!(
+    /// An `events ... end` block.
+    Events: "events"
+);
+
+impl<'t> Events<'t> {
+    pub fn attributes(&self) -> Option<Attributes<'t>> {
+        child(self.0)
+    }
+
+    pub fn identifiers(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        named_children(self.0).filter(|c| c.kind() == "identifier")
+    }
+}
+
+ast_node!(
+    /// An `enumeration ... end` block.
+    Enumeration: "enumeration"
+);
+
+impl<'t> Enumeration<'t> {
+    pub fn attributes(&self) -> Option<Attributes<'t>> {
+        child(self.0)
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = EnumMember<'t>> + 't {
+        children(self.0)
+    }
+}
+
+ast_node!(
+    /// A member of an enumeration, with the arguments of its constructor.
+    EnumMember: "enum"
+);
+
+impl<'t> EnumMember<'t> {
+    pub fn name(&self) -> Result<Node<'t>> {
+        named_children(self.0)
+            .next()
+            .ok_or_else(|| missing(&self.0))
+    }
+
+    pub fn arguments(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        named_children(self.0).skip(1)
+    }
+}
+
+ast_node!(
+    /// A matrix `[...]` or a cell array `{...}`.
+    Matrix: "matrix" | "cell"
+);
+
+impl<'t> Matrix<'t> {
+    pub fn is_cell(&self) -> bool {
+        self.0.kind() == "cell"
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Row<'t>> + 't {
+        children(self.0)
+    }
+}
+
+ast_node!(Row: "row");
+
+impl<'t> Row<'t> {
+    /// The elements of the row, without comments.
+    pub fn elements(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        named_children(self.0).filter(|c| !c.is_extra())
+    }
+}
+
+ast_node!(Assignment: "assignment");
+
+impl<'t> Assignment<'t> {
+    pub fn left(&self) -> Result<Node<'t>> {
+        field(self.0, "left")
+    }
+
+    pub fn right(&self) -> Result<Node<'t>> {
+        field(self.0, "right")
+    }
+}
+
+ast_node!(
+    /// A binary operator like `a + b`, a boolean one like `a && b`, or a
+    /// comparison like `a < b`.
+    Operation: "binary_operator" | "boolean_operator" | "comparison_operator"
+);
+
+impl<'t> Operation<'t> {
+    pub fn left(&self) -> Option<Node<'t>> {
+        self.0.named_child(0)
+    }
+
+    /// The operator token.
+    pub fn operator(&self) -> Option<Node<'t>> {
+        self.0.child_by_field_name("operator").or_else(|| {
+            (0..self.0.child_count())
+                .filter_map(|i| self.0.child(i))
+                .find(|c| !c.is_named())
+        })
+    }
+}
+
+ast_node!(
+    /// A call of a function or an indexing, with parentheses or braces.
+    FunctionCall: "function_call"
+);
+
+impl<'t> FunctionCall<'t> {
+    /// What is called or indexed: the children up to the opening bracket,
+    /// without line continuations.
+    pub fn callee(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        let node = self.0;
+        (0..node.child_count())
+            .filter_map(move |i| node.child(i))
+            .filter(|c| !LineContinuation::can_cast(c.kind()))
+            .take_while(|c| c.is_named() || !matches!(c.kind(), "(" | "{"))
+    }
+
+    /// Whether the arguments are in braces, as when indexing a cell array.
+    pub fn is_cell_index(&self) -> bool {
+        (0..self.0.child_count())
+            .filter_map(|i| self.0.child(i))
+            .find(|c| !c.is_named() && matches!(c.kind(), "(" | "{"))
+            .is_some_and(|c| c.kind() == "{")
+    }
+
+    pub fn arguments(&self) -> Option<Arguments<'t>> {
+        child(self.0)
+    }
+}
+
+ast_node!(
+    /// The arguments of a call or of an anonymous function.
+    Arguments: "arguments"
+);
+
+impl<'t> Arguments<'t> {
+    pub fn arguments(&self) -> impl Iterator<Item = Node<'t>> + 't {
+        named_children(self.0)
+    }
+}
+
+ast_node!(
+    /// An anonymous function like `@(x) x + 1`.
+    Lambda: "lambda"
+);
+
+impl<'t> Lambda<'t> {
+    pub fn arguments(&self) -> Option<Arguments<'t>> {
+        child(self.0)
+    }
+
+    pub fn expression(&self) -> Result<Node<'t>> {
+        field(self.0, "expression")
+    }
+}
+
+ast_node!(
+    /// A command like `hold on`, whose arguments are words.
+    Command: "command"
+);
+
+impl<'t> Command<'t> {
+    pub fn name(&self) -> Result<CommandName<'t>> {
+        child(self.0).ok_or_else(|| missing(&self.0))
+    }
+}
+
+ast_node!(CommandName: "command_name");
@@ -4,23 +4,112 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use super::args::Arguments;
-use anyhow::{anyhow, Context, Result};
-use tree_sitter::Node;
+use super::ast::{
+    block_items, cast, comments_after, directives, non_directive_comments, ArgumentsStatement,
+    Assignment, AstNode, Attribute, Attributes, Block, ClassDefinition, Command, CommandName,
+    Comment, Dimensions, Enumeration, Events, ForStatement, FunctionArguments, FunctionCall,
+    FunctionDefinition, FunctionOutput, FunctionSignature, IfStatement, Lambda, LineContinuation,
+    Matrix, Methods, Operation, Properties, Property, SpmdStatement, SwitchStatement, TryStatement,
+    WhileStatement,
+};
+use super::diagnostic::{self, error_nodes, Diagnostic};
+use super::doc::{render, Doc};
+use super::document::{CacheKey, CachedStatement, StatementCache};
+use super::error::{FormatError, Result, Span};
+use super::formatter::{parse, Formatter};
+use super::idempotency::check_idempotent;
+use super::limits::Limits;
+use super::options::FormatOptions;
+use super::registry::{FormatterRegistry, NodeFormatter};
+use super::source_map::{Mapping, SourceMap};
+use super::verify;
+use super::width::{advance, display_width};
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::Range;
+use std::sync::Arc;
+use tree_sitter::{Node, Tree};
 
 struct State<'a> {
-    formatted: String,
-    arguments: &'a mut Arguments,
+    output: Vec<Doc>,
+    options: &'a FormatOptions,
+    in_range: bool,
     code: &'a [u8],
     col: usize,
     row: usize,
     level: usize,
     extra_indentation: usize,
     cell_size: Option<Vec<(usize, bool)>>,
+    untouched: Vec<Span>,
+    /// The input range of every formatted node and the marks around its
+    /// output, when recording a source map.
+    mappings: Option<Vec<(Range<usize>, usize, usize)>>,
+    marks: usize,
+    cache: Option<&'a mut StatementCache>,
+    registry: &'a FormatterRegistry,
+    /// Whether the last statement of the root keeps the separator it was
+    /// written with instead of getting a `;`, see [`format_fragment`].
+    ///
+    /// [`format_fragment`]: super::format_fragment
+    fragment: bool,
+    /// The matrix cells measured so far, by node id.
+    measured: HashMap<usize, MeasuredCell>,
+    /// Whether a matrix cell is being measured, see [`measure_cell`].
+    measuring: bool,
+    /// How many nodes are being formatted around the current one.
+    depth: usize,
+    limits: Limits,
+    /// The column the innermost fill is aligned to, see [`State::fill`].
+    align: Option<usize>,
+    /// Whether soft lines are printed flat, as in matrix cells aligned in
+    /// columns, which have to stay as wide as they were measured.
+    flat: bool,
 }
 
-impl State<'_> {
+/// A matrix cell formatted on its own to measure its column, see
+/// [`calculate_column_sizes`].
+struct MeasuredCell {
+    text: String,
+    col: usize,
+    /// The output, if it can be reused as it is when formatting the cell in
+    /// place. Only cells on a single line do not depend on where they are.
+    docs: Option<Vec<Doc>>,
+}
+
+impl<'a> State<'a> {
+    fn new(code: &'a str, options: &'a FormatOptions, registry: &'a FormatterRegistry) -> Self {
+        State {
+            options,
+            in_range: false,
+            code: code.as_bytes(),
+            col: 0,
+            row: 0,
+            level: 0,
+            extra_indentation: 0,
+            output: vec![],
+            cell_size: None,
+            untouched: vec![],
+            mappings: options.source_map().then(Vec::new),
+            marks: 0,
+            cache: None,
+            registry,
+            fragment: false,
+            measured: HashMap::new(),
+            measuring: false,
+            depth: 0,
+            limits: Limits::default(),
+            align: None,
+            flat: false,
+        }
+    }
+
     fn indent(&mut self) {
+        if self.align.is_some() {
+            // The layout starts the line at the fill, see `println`.
+            let col = 4 * self.level + self.extra_indentation;
+            self.print(&" ".repeat(col.saturating_sub(self.col)));
+            return;
+        }
         for _ in 0..self.level {
             self.print("    ");
         }
@@ -30,30 +119,152 @@ impl State<'_> {
     }
 
     fn print(&mut self, string: &str) {
-        if self.arguments.inplace {
-            self.formatted += string;
-        } else {
-            print!("{}", string);
+        if string.is_empty() {
+            return;
         }
-        self.col += string.len();
+        match self.output.last_mut() {
+            Some(Doc::Text(text)) => text.push_str(string),
+            _ => self.output.push(Doc::text(string)),
+        }
+        self.col = advance(self.col, string);
+    }
+
+    fn text(&self, node: Node) -> Result<&'a str> {
+        node.utf8_text(self.code)
+            .map_err(|_| FormatError::Encoding {
+                span: Span::of(&node),
+                kind: node.kind(),
+            })
     }
 
     fn print_node(&mut self, node: Node) -> Result<()> {
-        self.print(node.utf8_text(self.code)?);
+        self.print(self.text(node)?);
         Ok(())
     }
 
     fn println(&mut self, string: &str) {
-        if self.arguments.inplace {
-            self.formatted += string;
-            self.formatted += "\n";
-        } else {
-            println!("{}", string);
-        }
-        self.col = 0;
+        self.print(string);
+        self.output.push(Doc::HardLine);
+        self.col = self.align.unwrap_or(0);
         self.row += 1;
     }
 
+    /// Marks the current output position, when recording a source map.
+    fn mark(&mut self) -> Option<usize> {
+        self.mappings.as_ref()?;
+        let mark = self.marks;
+        self.marks += 1;
+        self.output.push(Doc::Mark(mark));
+        Some(mark)
+    }
+
+    /// Records that `node` was formatted into the output from `start` on.
+    fn record(&mut self, node: Node, start: Option<usize>) {
+        if let (Some(start), Some(end)) = (start, self.mark()) {
+            if let Some(mappings) = &mut self.mappings {
+                mappings.push((node.byte_range(), start, end));
+            }
+        }
+    }
+
+    /// Runs `format` and returns what it printed instead of adding it to the
+    /// output.
+    fn capture(&mut self, format: impl FnOnce(&mut Self) -> Result<()>) -> Result<Vec<Doc>> {
+        let outer = std::mem::take(&mut self.output);
+        let result = format(self);
+        let captured = std::mem::replace(&mut self.output, outer);
+        result.map(|_| captured)
+    }
+
+    /// Prints `flat`, or a `...` continuation and a line break if what follows
+    /// does not fit into the maximum width anymore.
+    ///
+    /// Only the layout knows which lines are broken, so `col` moves on as if
+    /// `flat` was printed. Output that depends on the actual column is placed
+    /// relative to the enclosing fill, see [`State::fill`].
+    fn soft_line(&mut self, flat: &'static str) {
+        if self.options.max_width().is_none() || self.flat {
+            self.print(flat);
+            return;
+        }
+        self.output.push(Doc::line(flat, " ..."));
+        self.col += flat.len();
+    }
+
+    /// Runs `format`, taking all of its line breaks or none of them.
+    fn group(&mut self, format: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if self.options.max_width().is_none() {
+            return format(self);
+        }
+        let docs = self.capture(format)?;
+        self.output.push(Doc::group(docs));
+        Ok(())
+    }
+
+    /// Runs `format` for a statement of a block. The statement is laid out as
+    /// a group, and the lines it continues on are indented like the block.
+    fn statement(&mut self, format: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if self.options.max_width().is_none() {
+            return format(self);
+        }
+        let indent = 4 * self.level;
+        let outer = self.align.replace(indent);
+        let docs = self.capture(|state| state.group(format));
+        self.align = outer;
+        let width = indent.saturating_sub(outer.unwrap_or(0));
+        self.output.push(Doc::indent(width, docs?));
+        Ok(())
+    }
+
+    /// Runs `format`, breaking its soft lines one by one where needed.
+    ///
+    /// Broken lines, and lines started by hard line breaks, continue where
+    /// continuation lines are indented to. If that is where the output starts,
+    /// the fill is aligned to it, otherwise the lines follow the enclosing
+    /// fill, e.g. for an operator chain in the arguments of a call.
+    fn fill(&mut self, format: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if self.options.max_width().is_none() {
+            return format(self);
+        }
+        let aligned = self.col == 4 * self.level + self.extra_indentation;
+        let outer = self.align;
+        if aligned {
+            self.align = Some(self.col);
+        }
+        let docs = self.capture(format);
+        self.align = outer;
+        let docs = docs?;
+        self.output.push(Doc::fill(if aligned {
+            vec![Doc::align(docs)]
+        } else {
+            docs
+        }));
+        Ok(())
+    }
+
+    /// Whether `node` is a bare `...` continuation. When wrapping, these are
+    /// dropped and the line breaks are chosen anew.
+    fn is_soft_continuation(&self, node: Node) -> bool {
+        self.options.max_width().is_some()
+            && LineContinuation::can_cast(node.kind())
+            && self.text(node).is_ok_and(|text| text.trim() == "...")
+    }
+
+    /// Lays out the output against the maximum width.
+    fn render(&self, col: usize) -> (String, Vec<usize>) {
+        let width = self.options.max_width().unwrap_or(usize::MAX);
+        render(&self.output, width, col)
+    }
+
+    /// How far the output is right of the indentation of the current block,
+    /// e.g. to align continuation lines. Fails on `node` if the output is
+    /// left of the indentation, which is a bug in the formatter.
+    fn offset(&self, node: &Node) -> Result<usize> {
+        self.col
+            .checked_sub(4 * self.level)
+            .ok_or_else(|| FormatError::internal(node, "output is left of the indentation"))
+    }
+
     fn maybe_set_extra_indentation(&mut self, value: usize) {
         if self.extra_indentation == 0 {
             self.extra_indentation = value;
@@ -67,179 +278,436 @@ trait TraversingError<T> {
 
 impl<T> TraversingError<T> for Option<T> {
     fn err_at_loc(self, node: &Node) -> Result<T> {
-        self.ok_or_else(|| {
-            anyhow!(
-                "Error accessing token around line {} col {}",
-                node.range().start_point.row,
-                node.range().start_point.column
-            )
-        })
+        self.ok_or_else(|| FormatError::internal(node, "expected child node is missing"))
+    }
+}
+
+/// Where a [`NodeFormatter`] prints to, keeping track of the position in the
+/// output and of the indentation.
+pub struct FormatContext<'s, 'a> {
+    state: &'s mut State<'a>,
+}
+
+impl<'a> FormatContext<'_, 'a> {
+    pub fn options(&self) -> &FormatOptions {
+        self.state.options
+    }
+
+    /// The source text of `node`.
+    pub fn text(&self, node: Node) -> Result<&'a str> {
+        self.state.text(node)
+    }
+
+    /// Prints text that does not contain line breaks.
+    pub fn print(&mut self, text: &str) {
+        self.state.print(text);
+    }
+
+    /// Prints text that does not contain line breaks, then a line break.
+    pub fn println(&mut self, text: &str) {
+        self.state.println(text);
+    }
+
+    /// Prints the indentation of the current level.
+    pub fn indent(&mut self) {
+        self.state.indent();
+    }
+
+    /// The indentation level, in units of four spaces.
+    pub fn level(&self) -> usize {
+        self.state.level
+    }
+
+    pub fn set_level(&mut self, level: usize) {
+        self.state.level = level;
+    }
+
+    /// The column the output is at.
+    pub fn column(&self) -> usize {
+        self.state.col
+    }
+
+    /// Formats `node` with the formatter registered for its kind.
+    pub fn format(&mut self, node: Node) -> Result<()> {
+        format_node(self.state, node)
+    }
+
+    /// Formats `node` with the built-in formatter for its kind, e.g. to
+    /// decorate it from a formatter registered in its place.
+    pub fn format_builtin(&mut self, node: Node) -> Result<()> {
+        match BUILTINS.iter().find(|(kind, _)| *kind == node.kind()) {
+            Some((_, format)) => format(self.state, node),
+            None => self.state.print_node(node),
+        }
     }
 }
 
-pub fn beautify(code: &str, arguments: &mut Arguments) -> Result<String> {
-    let mut parser = tree_sitter::Parser::new();
-    parser
-        .set_language(&tree_sitter_matlab::LANGUAGE.into())
-        .with_context(|| "Could not set Tree-Sitter language")?;
+/// Formats `code` and returns the beautified source.
+///
+/// Nothing is written anywhere: the whole file is formatted in memory and only
+/// returned once formatting succeeded. To format many files, create a
+/// [`Formatter`] once and reuse it instead.
+pub fn beautify(code: &str, options: &FormatOptions) -> Result<String> {
+    Formatter::new(options.clone()).format_str(code)
+}
 
-    let tree = parser
-        .parse(code, None)
-        .ok_or_else(|| anyhow!("Could not parse file."))?;
+/// Formats `code` and writes the result into `writer`.
+///
+/// The output is only written after the whole file has been formatted, so a
+/// failure never leaves partial output in the sink.
+pub fn beautify_to<W: Write>(code: &str, options: &FormatOptions, writer: &mut W) -> Result<()> {
+    let formatted = beautify(code, options)?;
+    writer.write_all(formatted.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
 
+/// The result of formatting, with details beyond the formatted text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formatted {
+    pub text: String,
+    /// Regions of the input that contain syntax errors and were copied through
+    /// unchanged. Only ever filled in tolerant mode.
+    pub untouched: Vec<Span>,
+    /// The syntax errors found in the input. Only ever filled in tolerant mode.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Only recorded when enabled in the options.
+    pub source_map: Option<SourceMap>,
+}
+
+/// Fails with all syntax errors in the tree, unless in tolerant mode.
+pub(crate) fn check_syntax(root: Node, code: &str, options: &FormatOptions) -> Result<()> {
+    if root.has_error() && !options.tolerant() {
+        let node = error_nodes(root).first().copied().unwrap_or(root);
+        return Err(FormatError::Syntax {
+            span: Span::of(&node),
+            kind: node.kind(),
+            diagnostics: diagnostic::collect(root, code),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn format_tree(
+    code: &str,
+    tree: &Tree,
+    options: &FormatOptions,
+    registry: &FormatterRegistry,
+    limits: &Limits,
+) -> Result<Formatted> {
+    let mut state = State::new(code, options, registry);
+    state.limits = limits.clone();
+    format_tree_with(code, state, tree)
+}
+
+/// Formats a whole tree, reusing the output of top-level statements found in
+/// `cache` and storing the output of the others there.
+pub(crate) fn format_tree_cached<'a>(
+    code: &'a str,
+    tree: &Tree,
+    options: &'a FormatOptions,
+    cache: &'a mut StatementCache,
+    limits: &Limits,
+) -> Result<Formatted> {
+    let mut state = State::new(code, options, FormatterRegistry::builtin());
+    state.cache = Some(cache);
+    state.limits = limits.clone();
+    format_tree_with(code, state, tree)
+}
+
+fn format_tree_with(code: &str, mut state: State, tree: &Tree) -> Result<Formatted> {
     let root = tree.root_node();
-    if root.has_error() {
-        return Err(anyhow!("Parsed file contain errors."));
-    }
-
-    let mut state = State {
-        arguments,
-        code: code.as_bytes(),
-        col: 0,
-        row: 0,
-        level: 0,
-        extra_indentation: 0,
-        formatted: String::with_capacity(code.len() * 2),
-        cell_size: None,
-    };
+    check_syntax(root, code, state.options)?;
+    format_block(&mut state, root)?;
+    let (text, marks) = state.render(0);
+    if state.options.verify() {
+        let output = parse(&text)?;
+        verify::compare(root, code, output.root_node(), &text)?;
+    }
+    if state.options.ensure_idempotent() {
+        check_idempotent(&text, state.options, |tree, options| {
+            let again = format_tree(&text, tree, options, state.registry, &state.limits)?;
+            Ok(again.text)
+        })?;
+    }
+    let source_map = state.mappings.map(|mappings| {
+        let mappings = mappings
+            .into_iter()
+            .map(|(input, start, end)| Mapping {
+                input,
+                output: marks[start]..marks[end],
+            })
+            .collect();
+        SourceMap::new(code.len(), text.len(), mappings)
+    });
+    Ok(Formatted {
+        text,
+        untouched: state.untouched,
+        diagnostics: diagnostic::collect(root, code),
+        source_map,
+    })
+}
 
+/// Formats `code` as statements nested `level` levels deep, see
+/// [`format_fragment`].
+///
+/// [`format_fragment`]: super::format_fragment
+pub(crate) fn format_fragment_tree(
+    code: &str,
+    tree: &Tree,
+    level: usize,
+    options: &FormatOptions,
+    registry: &FormatterRegistry,
+    limits: &Limits,
+) -> Result<String> {
+    let root = tree.root_node();
+    check_syntax(root, code, options)?;
+    let mut state = State::new(code, options, registry);
+    state.limits = limits.clone();
+    state.level = level;
+    state.fragment = !options.terminate_fragments();
     format_block(&mut state, root)?;
-    Ok(state.formatted)
+    let mut text = state.render(0).0;
+    if text.trim().is_empty() {
+        text.clear();
+    } else if state.fragment {
+        text.pop();
+    }
+    Ok(text)
+}
+
+/// Formats a single statement nested `level` levels deep, including its
+/// indentation when it starts a line and its terminating `;` or `,`. A
+/// statement further along a line is rendered from column `col` on, which
+/// must not be left of the indentation.
+pub(crate) fn format_statement(
+    code: &str,
+    node: Node,
+    level: usize,
+    col: Option<usize>,
+    options: &FormatOptions,
+    registry: &FormatterRegistry,
+    limits: &Limits,
+) -> Result<String> {
+    let mut state = State::new(code, options, registry);
+    state.limits = limits.clone();
+    state.level = level;
+    state.col = col.unwrap_or(0);
+    if col.is_none() {
+        state.indent();
+    }
+    let verbatim = options.tolerant() && !can_format_around_errors(node);
+    state.statement(|state| {
+        if verbatim {
+            print_verbatim(state, node)
+        } else {
+            format_node(state, node)
+        }
+    })?;
+    if verbatim {
+        if let (true, Some(separator)) = (is_terminated(node), statement_separator(node)) {
+            state.print_node(separator)?;
+        }
+    }
+    if !verbatim && is_terminated(node) {
+        match statement_separator(node) {
+            Some(separator) if separator.kind() == "," => state.print(","),
+            _ => state.print(";"),
+        }
+    }
+    Ok(state.render(col.unwrap_or(0)).0)
+}
+
+/// The `;` or `,` terminating a statement in the source, if any.
+pub(crate) fn statement_separator(node: Node) -> Option<Node> {
+    node.next_sibling()
+        .filter(|n| !n.is_named() && (n.kind() == ";" || n.kind() == ","))
+}
+
+/// Whether a statement is terminated by `;` when formatted.
+pub(crate) fn is_terminated(node: Node) -> bool {
+    !STATEMENTS.contains(&node.kind())
 }
 
 fn format_node(state: &mut State, node: Node) -> Result<()> {
-    match node.kind() {
-        "arguments_statement" => format_arguments_statement(state, node),
-        "assignment" => format_assignment(state, node),
-        "binary_operator" => format_binary(state, node),
-        "block" => format_block(state, node),
-        "boolean_operator" => format_boolean(state, node),
-        "cell" => format_matrix(state, node),
-        "class_definition" => format_classdef(state, node),
-        "command" => format_command(state, node),
-        "comment" => format_comment(state, node),
-        "comparison_operator" => format_boolean(state, node),
-        "field_expression" => format_field(state, node),
-        "for_statement" => format_for(state, node),
-        "function_call" => format_fncall(state, node),
-        "function_definition" => format_function(state, node),
-        "global_operator" => format_global(state, node),
-        "handle_operator" => format_unary(state, node),
-        "if_statement" => format_if(state, node),
-        "indirect_access" => format_parenthesis(state, node),
-        "lambda" => format_lambda(state, node),
-        "line_continuation" => format_line_continuation(state, node),
-        "matrix" => format_matrix(state, node),
-        "metaclass_operator" => format_unary(state, node),
-        "multioutput_variable" => format_multioutput(state, node),
-        "not_operator" => format_unary(state, node),
-        "parenthesis" => format_parenthesis(state, node),
-        "persistent_operator" => format_global(state, node),
-        "postfix_operator" => format_unary(state, node),
-        "property" => format_property(state, node),
-        "property_name" => format_property_name(state, node),
-        "range" => format_range(state, node),
-        "row" => format_row(state, node),
-        "spmd_statement" => format_spmd(state, node),
-        "switch_statement" => format_switch(state, node),
-        "try_statement" => format_try(state, node),
-        "unary_operator" => format_unary(state, node),
-        "while_statement" => format_while(state, node),
-        _ => state.print_node(node),
+    let limit = state.options.max_depth();
+    if state.depth >= limit {
+        return Err(FormatError::TooDeep {
+            span: Span::of(&node),
+            kind: node.kind(),
+            limit,
+        });
+    }
+    state.depth += 1;
+    let start = state.mark();
+    let result = format_kind(state, node);
+    state.depth -= 1;
+    result?;
+    state.record(node, start);
+    Ok(())
+}
+
+fn format_kind(state: &mut State, node: Node) -> Result<()> {
+    let registry = state.registry;
+    match registry.get(node.kind()) {
+        Some(formatter) => formatter.format(&mut FormatContext { state }, node),
+        None => state.print_node(node),
     }
 }
 
+type BuiltinFormatter = fn(&mut State, Node) -> Result<()>;
+
+/// The formatters registered by default, by node kind.
+const BUILTINS: [(&str, BuiltinFormatter); 37] = [
+    ("arguments", format_arguments),
+    ("arguments_statement", format_arguments_statement),
+    ("assignment", format_assignment),
+    ("binary_operator", format_binary),
+    ("block", format_block),
+    ("boolean_operator", format_boolean),
+    ("cell", format_matrix),
+    ("class_definition", format_classdef),
+    ("command", format_command),
+    ("comment", format_comment),
+    ("comparison_operator", format_boolean),
+    ("field_expression", format_field),
+    ("for_statement", format_for),
+    ("function_call", format_fncall),
+    ("function_definition", format_function),
+    ("global_operator", format_global),
+    ("handle_operator", format_unary),
+    ("if_statement", format_if),
+    ("indirect_access", format_parenthesis),
+    ("lambda", format_lambda),
+    ("line_continuation", format_line_continuation),
+    ("matrix", format_matrix),
+    ("metaclass_operator", format_unary),
+    ("multioutput_variable", format_multioutput),
+    ("not_operator", format_unary),
+    ("parenthesis", format_parenthesis),
+    ("persistent_operator", format_global),
+    ("postfix_operator", format_unary),
+    ("property", format_property),
+    ("property_name", format_property_name),
+    ("range", format_range),
+    ("row", format_row),
+    ("spmd_statement", format_spmd),
+    ("switch_statement", format_switch),
+    ("try_statement", format_try),
+    ("unary_operator", format_unary),
+    ("while_statement", format_while),
+];
+
+struct Builtin(BuiltinFormatter);
+
+impl NodeFormatter for Builtin {
+    fn format(&self, context: &mut FormatContext, node: Node) -> Result<()> {
+        (self.0)(context.state, node)
+    }
+}
+
+pub(crate) fn builtin_formatters() -> impl Iterator<Item = (&'static str, Arc<dyn NodeFormatter>)> {
+    BUILTINS
+        .iter()
+        .map(|&(kind, format)| (kind, Arc::new(Builtin(format)) as Arc<dyn NodeFormatter>))
+}
+
+/// Statements that are not terminated by `;`.
+const STATEMENTS: [&str; 10] = [
+    "arguments_statement",
+    "class_definition",
+    "comment",
+    "for_statement",
+    "function_definition",
+    "if_statement",
+    "spmd_statement",
+    "switch_statement",
+    "try_statement",
+    "while_statement",
+];
+
 fn format_block(state: &mut State, node: Node) -> Result<()> {
-    let statements = [
-        "arguments_statement",
-        "class_definition",
-        "comment",
-        "for_statement",
-        "function_definition",
-        "if_statement",
-        "spmd_statement",
-        "switch_statement",
-        "try_statement",
-        "while_statement",
-    ];
-    let mut cursor = node.walk();
     let original_indentation = state.level;
     let indents = ["cvx_begin", "subject"];
     let dedents = ["cvx_end"];
     state.extra_indentation = 0;
     state.indent();
-    let mut named_children: Vec<Node> = node.named_children(&mut cursor).collect();
-    let mut prev_node = node;
-    while let Some(n) = prev_node.prev_named_sibling() {
-        prev_node = n;
-        if n.kind() == "comment" {
-            if n.utf8_text(state.code)?.starts_with("%#") {
-                continue;
-            }
-            named_children.insert(0, n);
-        } else {
-            break;
-        }
-    }
-    let mut next_node = node;
-    while let Some(n) = next_node.next_named_sibling() {
-        next_node = n;
-        if n.kind() == "comment" {
-            named_children.push(n);
-        } else {
-            break;
-        }
-    }
+    let named_children = block_items(node, state.code);
+    let last_statement = named_children
+        .iter()
+        .rposition(|c| !Comment::can_cast(c.kind()));
     for (i, child) in named_children.iter().enumerate() {
+        state.limits.check()?;
         let previous = if i > 0 {
             named_children.get(i - 1)
         } else {
             None
         };
         let next = named_children.get(i + 1);
-        if child.kind() == "command" {
-            let command_name = child
-                .named_child(0)
-                .err_at_loc(child)?
-                .utf8_text(state.code)?;
+        if let Some(command) = Command::cast(*child) {
+            let command_name = state.text(command.name()?.syntax())?;
             if dedents.contains(&command_name) {
                 state.level = original_indentation;
             }
         }
         if let Some(previous) = previous {
             // There are some empty lines between nodes. Preserve one of them.
-            if child.range().start_point.row - previous.range().end_point.row > 1
-                || child.kind() == "function_definition"
+            if child
+                .range()
+                .start_point
+                .row
+                .saturating_sub(previous.range().end_point.row)
+                > 1
+                || FunctionDefinition::can_cast(child.kind())
             {
                 state.println("");
             }
             // Only assignments and comments are allowed on the same line.
-            if !(child.kind() == "assignment" && previous.kind() == "assignment")
-                && child.kind() != "comment"
-                || child.range().start_point.row != previous.range().end_point.row
-            {
+            let same_line = Comment::can_cast(child.kind())
+                || Assignment::can_cast(child.kind()) && Assignment::can_cast(previous.kind());
+            if !same_line || child.range().start_point.row != previous.range().end_point.row {
                 state.println("");
                 state.indent();
             }
         }
-        format_node(state, *child)?;
+        let verbatim = state.options.tolerant() && !can_format_around_errors(*child);
+        state.statement(|state| {
+            if verbatim {
+                print_verbatim(state, *child)
+            } else if node.parent().is_none() {
+                format_top_level(state, *child)
+            } else {
+                format_node(state, *child)
+            }
+        })?;
         state.extra_indentation = 0;
-        if child.kind() == "command" {
-            let command_name = child
-                .named_child(0)
-                .err_at_loc(child)?
-                .utf8_text(state.code)?;
+        if let Some(command) = Command::cast(*child) {
+            let command_name = state.text(command.name()?.syntax())?;
             if indents.contains(&command_name) {
                 state.level += 1;
             }
         }
-        // Some statements don't have ; at the end, like if, for, while, etc.
-        if !statements.contains(&child.kind()) {
+        if verbatim {
+            // Keep whatever separator the broken statement had.
+            if let Some(separator) = statement_separator(*child) {
+                state.print_node(separator)?;
+            }
+        } else if state.fragment
+            && node.parent().is_none()
+            && last_statement == Some(i)
+            && !STATEMENTS.contains(&child.kind())
+        {
+            // Keep the separator the last statement was written with, if any.
+            if let Some(separator) = statement_separator(*child) {
+                state.print_node(separator)?;
+            }
+        } else if !STATEMENTS.contains(&child.kind()) {
+            // Some statements don't have ; at the end, like if, for, while, etc.
             if let Some(next) = next {
                 // If the current and next nodes are both assignments and on the same line, then
                 // separate with , instead of ;
-                if child.kind() == "assignment"
-                    && next.kind() == "assignment"
+                if Assignment::can_cast(child.kind())
+                    && Assignment::can_cast(next.kind())
                     && child.range().end_point.row == next.range().start_point.row
                 {
                     state.print(", ");
@@ -257,15 +725,109 @@ fn format_block(state: &mut State, node: Node) -> Result<()> {
     Ok(())
 }
 
+/// Formats a statement of the root block, reusing its output from the cache
+/// when the same statement was formatted at the same place before.
+fn format_top_level(state: &mut State, node: Node) -> Result<()> {
+    if state.cache.is_none() || state.mappings.is_some() {
+        return format_node(state, node);
+    }
+    let key = CacheKey {
+        range: node.byte_range(),
+        level: state.level,
+        col: state.col,
+    };
+    if let Some(cached) = state.cache.as_mut().and_then(|cache| cache.get(&key)) {
+        state.output.extend(cached.docs.iter().cloned());
+        state.col = cached.col;
+        state.row += cached.rows;
+        return Ok(());
+    }
+    let row = state.row;
+    let untouched = state.untouched.len();
+    let docs = state.capture(|state| format_node(state, node))?;
+    state.output.extend(docs.iter().cloned());
+    // Statements with parts copied through in tolerant mode are formatted
+    // every time, so the untouched spans are reported like without a cache.
+    if state.untouched.len() != untouched {
+        return Ok(());
+    }
+    let cached = CachedStatement {
+        docs,
+        col: state.col,
+        rows: state.row - row,
+    };
+    if let Some(cache) = &mut state.cache {
+        cache.insert(key, cached);
+    }
+    Ok(())
+}
+
+/// Whether a statement can be formatted even though it may contain syntax
+/// errors, because all of them are inside nested blocks, which take care of
+/// their own broken statements.
+fn can_format_around_errors(node: Node) -> bool {
+    let clauses = [
+        "case_clause",
+        "catch_clause",
+        "else_clause",
+        "elseif_clause",
+        "function_definition",
+        "methods",
+        "otherwise_clause",
+    ];
+    if node.is_error() || node.is_missing() {
+        return false;
+    }
+    if !node.has_error() {
+        return true;
+    }
+    let mut cursor = node.walk();
+    let result = node.children(&mut cursor).all(|child| {
+        Block::can_cast(child.kind())
+            || !child.has_error()
+            || clauses.contains(&child.kind()) && can_format_around_errors(child)
+    });
+    result
+}
+
+/// Copies a statement containing syntax errors through unchanged.
+fn print_verbatim(state: &mut State, node: Node) -> Result<()> {
+    let text = state.text(node)?;
+    let start = state.mark();
+    state.untouched.push(Span::of(&node));
+    print_formatted(state, text);
+    state.record(node, start);
+    Ok(())
+}
+
+/// Appends text that may span several lines, keeping track of the position.
+/// The lines are kept as they are, without indenting them.
+fn print_formatted(state: &mut State, text: &str) {
+    let Some(last) = text.rfind('\n') else {
+        state.print(text);
+        return;
+    };
+    match state.output.last_mut() {
+        Some(Doc::Text(output)) => output.push_str(text),
+        _ => state.output.push(Doc::text(text)),
+    }
+    state.row += text.matches('\n').count();
+    state.col = advance(0, &text[last + 1..]);
+}
+
 fn format_comment(state: &mut State, node: Node) -> Result<()> {
-    let text = node.utf8_text(state.code).unwrap();
+    let text = state.text(node)?;
     if node.range().start_point.row != node.range().end_point.row {
         if text.starts_with("%{") {
-            let lines: Vec<&str> = text
-                .strip_prefix("%{")
-                .unwrap_or(text)
-                .strip_suffix("%}")
-                .unwrap()
+            let body = text.strip_prefix("%{").unwrap_or(text);
+            let body =
+                body.strip_suffix("%}")
+                    .ok_or_else(|| FormatError::UnsupportedConstruct {
+                        span: Span::of(&node),
+                        kind: node.kind(),
+                        reason: "block comment is not closed by %}",
+                    })?;
+            let lines: Vec<&str> = body
                 .split('\n')
                 .map(|l| l.trim())
                 .filter(|l| !l.is_empty())
@@ -292,7 +854,7 @@ fn format_comment(state: &mut State, node: Node) -> Result<()> {
                 let line = line.trim();
                 if i != 0 {
                     state.println("");
-                    state.print(" ".repeat(col).as_str());
+                    state.print(" ".repeat(col.saturating_sub(state.col)).as_str());
                 }
                 state.print("%");
                 if !line.is_empty() {
@@ -323,82 +885,137 @@ fn format_comment(state: &mut State, node: Node) -> Result<()> {
 
 fn format_line_continuation(state: &mut State, node: Node) -> Result<()> {
     state.print(" ");
-    state.print_node(node)?;
-    if node.range().start_point.row == node.range().end_point.row {
-        state.println("");
-    } else {
-        state.col = 0;
-        state.row += 1;
-    }
+    // The line break is printed on its own, so that the layout indents the
+    // next line when wrapping.
+    state.println(state.text(node)?.trim_end_matches(['\r', '\n']));
     state.indent();
     Ok(())
 }
 
 fn format_assignment(state: &mut State, node: Node) -> Result<()> {
-    let lhs = node.child_by_field_name("left").err_at_loc(&node)?;
-    let rhs = node.child_by_field_name("right").err_at_loc(&node)?;
-    format_node(state, lhs)?;
+    let assignment: Assignment = cast(node)?;
+    format_node(state, assignment.left()?)?;
     state.print(" = ");
-    format_node(state, rhs)?;
+    format_node(state, assignment.right()?)?;
     state.extra_indentation = 0;
     Ok(())
 }
 
 fn format_binary(state: &mut State, node: Node) -> Result<()> {
-    state.maybe_set_extra_indentation(state.col - 4 * state.level);
+    state.maybe_set_extra_indentation(state.offset(&node)?);
+    state.fill(|state| format_operator_chain(state, node, print_binary_operator))
+}
+
+fn print_binary_operator(state: &mut State, node: Node, line_cont: bool) -> Result<()> {
     let add_ops = ["+", "-", ".+", ".-"];
-    let mut line_cont = false;
-    let mut cursor = node.walk();
-    for child in node.children(&mut cursor) {
-        if child.is_named() {
-            line_cont = child.kind() == "line_continuation";
-            format_node(state, child)?;
-        } else {
-            let operator = child.utf8_text(state.code)?.trim();
-            if state.arguments.sparse_math
-                || state.arguments.sparse_add && add_ops.contains(&operator)
-            {
-                if !line_cont {
-                    state.print(" ");
-                }
-                state.maybe_set_extra_indentation(state.col - 4 * state.level);
-                state.print(operator);
-                state.print(" ");
-            } else {
-                state.maybe_set_extra_indentation(state.col - 4 * state.level);
-                state.print(operator);
-            }
+    let operator = state.text(node)?.trim();
+    if state.options.sparse_math() && !state.in_range
+        || state.options.sparse_add() && add_ops.contains(&operator)
+    {
+        if !line_cont {
+            state.print(" ");
         }
+        state.maybe_set_extra_indentation(state.offset(&node)?);
+        state.print(operator);
+        state.soft_line(" ");
+    } else {
+        state.maybe_set_extra_indentation(state.offset(&node)?);
+        state.print(operator);
+        state.soft_line("");
     }
     Ok(())
 }
 
 fn format_boolean(state: &mut State, node: Node) -> Result<()> {
-    state.maybe_set_extra_indentation(state.col - 4 * state.level);
-    let mut line_cont = false;
-    let mut cursor = node.walk();
-    for child in node.children(&mut cursor) {
-        if child.is_named() {
-            line_cont = child.kind() == "line_continuation";
-            format_node(state, child)?;
-        } else {
-            let operator = child.utf8_text(state.code)?.trim();
-            if !line_cont {
-                state.print(" ");
+    state.maybe_set_extra_indentation(state.offset(&node)?);
+    state.fill(|state| format_operator_chain(state, node, print_boolean_operator))
+}
+
+fn print_boolean_operator(state: &mut State, node: Node, line_cont: bool) -> Result<()> {
+    if !line_cont {
+        state.print(" ");
+    }
+    state.maybe_set_extra_indentation(state.offset(&node)?);
+    state.print(state.text(node)?.trim());
+    state.soft_line(" ");
+    Ok(())
+}
+
+/// Formats the operands of a binary, boolean or comparison operator. Left
+/// operands with the same precedence are formatted along, so a chain of
+/// operators is wrapped at any of its operators before wrapping inside of the
+/// operands.
+///
+/// Chains are walked in a loop rather than recursively, as generated code can
+/// chain many thousands of terms, each one level deeper in the tree.
+fn format_operator_chain(
+    state: &mut State,
+    node: Node,
+    print_operator: fn(&mut State, Node, bool) -> Result<()>,
+) -> Result<()> {
+    let mut chain = vec![cast::<Operation>(node)?];
+    while let Some(child) = chain[chain.len() - 1].left().and_then(Operation::cast) {
+        if !same_precedence(state, chain[chain.len() - 1], child)? {
+            break;
+        }
+        chain.push(child);
+    }
+    // `format_node` records where `node` starts, the chain records the rest.
+    let starts: Vec<Option<usize>> = chain[1..].iter().map(|_| state.mark()).collect();
+    for (i, operation) in chain.iter().enumerate().rev() {
+        let operation = operation.syntax();
+        let inner = chain.get(i + 1).map(Operation::syntax);
+        let mut line_cont = false;
+        let mut cursor = operation.walk();
+        for child in operation.children(&mut cursor) {
+            if state.is_soft_continuation(child) || Some(child) == inner {
+                continue;
+            }
+            if child.is_named() {
+                line_cont = LineContinuation::can_cast(child.kind());
+                format_node(state, child)?;
+            } else {
+                print_operator(state, child, line_cont)?;
             }
-            state.maybe_set_extra_indentation(state.col - 4 * state.level);
-            state.print(operator);
-            state.print(" ");
+        }
+        if i != 0 {
+            state.record(operation, starts[i - 1]);
         }
     }
     Ok(())
 }
 
+/// Whether `child`, the left operand of `node`, is the same kind of operator
+/// with the same precedence, e.g. the `a + b` in `a + b - c`.
+fn same_precedence(state: &State, node: Operation, child: Operation) -> Result<bool> {
+    if child.syntax().kind() != node.syntax().kind() {
+        return Ok(false);
+    }
+    let (Some(operator), Some(child_operator)) = (node.operator(), child.operator()) else {
+        return Ok(false);
+    };
+    Ok(precedence(state.text(operator)?) == precedence(state.text(child_operator)?))
+}
+
+/// How tightly a binary operator binds. Higher binds tighter.
+fn precedence(operator: &str) -> u8 {
+    match operator.trim() {
+        "||" => 0,
+        "&&" => 1,
+        "|" => 2,
+        "&" => 3,
+        "<" | "<=" | ">" | ">=" | "==" | "~=" | "!=" => 4,
+        "+" | "-" | ".+" | ".-" => 5,
+        "*" | "/" | "\\" | ".*" | "./" | ".\\" => 6,
+        _ => 7,
+    }
+}
+
 fn format_unary(state: &mut State, node: Node) -> Result<()> {
     let mut cursor = node.walk();
     let children = node
         .children(&mut cursor)
-        .filter(|f| f.kind() != "line_continuation");
+        .filter(|f| !LineContinuation::can_cast(f.kind()));
     for child in children {
         format_node(state, child)?;
     }
@@ -409,10 +1026,10 @@ fn format_parenthesis(state: &mut State, node: Node) -> Result<()> {
     let mut cursor = node.walk();
     let child = node
         .named_children(&mut cursor)
-        .find(|c| c.kind() != "line_continuation")
+        .find(|c| !LineContinuation::can_cast(c.kind()))
         .err_at_loc(&node)?;
     state.print("(");
-    state.maybe_set_extra_indentation(state.col - 4 * state.level);
+    state.maybe_set_extra_indentation(state.offset(&node)?);
     format_node(state, child)?;
     state.print(")");
     Ok(())
@@ -422,16 +1039,16 @@ fn format_range(state: &mut State, node: Node) -> Result<()> {
     let mut cursor = node.walk();
     let children = node
         .named_children(&mut cursor)
-        .filter(|c| c.kind() != "line_continuation");
-    let sparse = state.arguments.sparse_math;
-    state.arguments.sparse_math = false;
+        .filter(|c| !LineContinuation::can_cast(c.kind()));
+    // Ranges are always printed dense, even with sparse math enabled.
+    let in_range = std::mem::replace(&mut state.in_range, true);
     for (i, child) in children.enumerate() {
         if i != 0 {
             state.print(":");
         }
         format_node(state, child)?;
     }
-    state.arguments.sparse_math = sparse;
+    state.in_range = in_range;
     Ok(())
 }
 
@@ -439,7 +1056,7 @@ fn format_multioutput(state: &mut State, node: Node) -> Result<()> {
     let mut cursor = node.walk();
     let children = node
         .named_children(&mut cursor)
-        .filter(|c| c.kind() != "line_continuation");
+        .filter(|c| !LineContinuation::can_cast(c.kind()));
     state.print("[");
     for (i, child) in children.enumerate() {
         if i != 0 {
@@ -452,16 +1069,12 @@ fn format_multioutput(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_lambda(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let arguments = node.children(&mut cursor).find(|c| c.kind() == "arguments");
-    let expression = node.child_by_field_name("expression").err_at_loc(&node)?;
+    let lambda: Lambda = cast(node)?;
+    let expression = lambda.expression()?;
     state.print("@");
     state.print("(");
-    if let Some(args) = arguments {
-        let children = args
-            .named_children(&mut cursor)
-            .filter(|c| c.kind() != "line_continuation");
-        for (i, arg) in children.enumerate() {
+    if let Some(args) = lambda.arguments() {
+        for (i, arg) in args.arguments().enumerate() {
             if i != 0 {
                 state.print(", ");
             }
@@ -474,53 +1087,52 @@ fn format_lambda(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_fncall(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let children = node.children(&mut cursor);
-    let mut parens = true;
-    for child in children {
-        if child.kind() == "line_continuation" {
-            continue;
+    state.group(|state| {
+        let call: FunctionCall = cast(node)?;
+        for child in call.callee() {
+            format_node(state, child)?;
         }
-        if !child.is_named() {
-            if child.utf8_text(state.code)? == "(" {
-                break;
-            } else if child.utf8_text(state.code)? == "{" {
-                parens = false;
-                break;
-            }
+        let parens = !call.is_cell_index();
+        if parens {
+            state.print("(");
+        } else {
+            state.print("{");
         }
-        format_node(state, child)?;
-    }
-    if parens {
-        state.print("(");
-    } else {
-        state.print("{");
-    }
-    let prev_extra = state.extra_indentation;
-    state.extra_indentation = state.col - 4 * state.level;
-    let arguments = node.children(&mut cursor).find(|c| c.kind() == "arguments");
-    if let Some(args) = arguments {
-        format_arguments(state, args)?;
-    }
-    if parens {
-        state.print(")");
-    } else {
-        state.print("}");
-    }
-    state.extra_indentation = prev_extra;
-    Ok(())
+        let prev_extra = state.extra_indentation;
+        state.extra_indentation = state.offset(&node)?;
+        if let Some(args) = call.arguments() {
+            format_node(state, args.syntax())?;
+        }
+        if parens {
+            state.print(")");
+        } else {
+            state.print("}");
+        }
+        state.extra_indentation = prev_extra;
+        Ok(())
+    })
 }
 
 fn format_arguments(state: &mut State, node: Node) -> Result<()> {
     let mut cursor = node.walk();
-    let children: Vec<Node> = node.named_children(&mut cursor).collect();
-    for (i, child) in children.iter().enumerate() {
-        if i != 0 && children.get(i - 1).unwrap().kind() != "line_continuation" {
-            state.print(", ");
+    let children: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|c| !state.is_soft_continuation(*c))
+        .collect();
+    state.fill(|state| {
+        for (i, child) in children.iter().enumerate() {
+            if i != 0 && !LineContinuation::can_cast(children[i - 1].kind()) {
+                if LineContinuation::can_cast(child.kind()) {
+                    state.print(", ");
+                } else {
+                    state.print(",");
+                    state.soft_line(" ");
+                }
+            }
+            format_node(state, *child)?;
         }
-        format_node(state, *child)?;
-    }
-    Ok(())
+        Ok(())
+    })
 }
 
 fn format_command(state: &mut State, node: Node) -> Result<()> {
@@ -530,8 +1142,8 @@ fn format_command(state: &mut State, node: Node) -> Result<()> {
             state.print(" ");
         }
         format_node(state, child)?;
-        if child.kind() == "command_name" {
-            state.extra_indentation = state.col - 4 * state.level;
+        if CommandName::can_cast(child.kind()) {
+            state.extra_indentation = state.offset(&child)?;
         }
     }
     state.extra_indentation = 0;
@@ -551,79 +1163,120 @@ fn format_field(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_matrix(state: &mut State, node: Node) -> Result<()> {
-    let matrix = node.kind() == "matrix";
-    let multiline = node.range().start_point.row != node.range().end_point.row;
-    let saved_cell_sizes = state.cell_size.clone();
-    state.cell_size = None;
-    if multiline {
-        calculate_column_sizes(state, node)?;
-    }
-    let mut cursor = node.walk();
-    if matrix {
-        state.print("[");
-    } else {
-        state.print("{");
-    }
-    let prev_extra = state.extra_indentation;
-    state.extra_indentation = state.col - 4 * state.level;
-    let mut first = true;
-    for child in node.named_children(&mut cursor) {
-        if child.kind() == "comment" {
-            if !first {
-                state.print(";");
-            }
-            format_comment(state, child)?;
-            state.println("");
-            state.indent();
-            first = true;
-            continue;
+    state.group(|state| {
+        let matrix = !cast::<Matrix>(node)?.is_cell();
+        let multiline = is_multiline(state, node);
+        let saved_cell_sizes = state.cell_size.clone();
+        let saved_flat = state.flat;
+        state.cell_size = None;
+        if multiline {
+            state.flat = true;
+            calculate_column_sizes(state, node)?;
         }
-        if !first {
-            if multiline {
-                state.println(";");
-                state.indent();
-            } else {
-                state.print("; ");
-            }
+        let mut cursor = node.walk();
+        if matrix {
+            state.print("[");
+        } else {
+            state.print("{");
         }
-        format_node(state, child)?;
-        if !child.is_extra() {
-            first = false;
+        let prev_extra = state.extra_indentation;
+        state.extra_indentation = state.offset(&node)?;
+        let children: Vec<Node> = node
+            .named_children(&mut cursor)
+            .filter(|c| !state.is_soft_continuation(*c))
+            .collect();
+        state.fill(|state| {
+            let mut first = true;
+            for child in children {
+                if Comment::can_cast(child.kind()) {
+                    if !first {
+                        state.print(";");
+                    }
+                    format_node(state, child)?;
+                    state.println("");
+                    state.indent();
+                    first = true;
+                    continue;
+                }
+                if !first {
+                    if multiline {
+                        state.println(";");
+                        state.indent();
+                    } else {
+                        state.print(";");
+                        state.soft_line(" ");
+                    }
+                }
+                format_node(state, child)?;
+                if !child.is_extra() {
+                    first = false;
+                }
+            }
+            Ok(())
+        })?;
+        if matrix {
+            state.print("]");
+        } else {
+            state.print("}");
         }
+        state.extra_indentation = prev_extra;
+        state.cell_size = saved_cell_sizes;
+        state.flat = saved_flat;
+        Ok(())
+    })
+}
+
+/// Whether a matrix or cell is written over several lines, with its columns
+/// aligned. When wrapping, lines only joined by `...` do not count, as the
+/// continuations are placed anew.
+fn is_multiline(state: &State, node: Node) -> bool {
+    if state.options.max_width().is_none() {
+        return node.range().start_point.row != node.range().end_point.row;
     }
-    if matrix {
-        state.print("]");
-    } else {
-        state.print("}");
-    }
-    state.extra_indentation = prev_extra;
-    state.cell_size = saved_cell_sizes;
-    Ok(())
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|c| !state.is_soft_continuation(*c))
+        .collect();
+    children.iter().any(|c| Comment::can_cast(c.kind()))
+        || children.windows(2).any(|pair| {
+            let gap = &state.code[pair[0].end_byte()..pair[1].start_byte()];
+            let mut lines = gap.split(|&b| b == b'\n');
+            lines.next_back();
+            lines.any(|line| !line.windows(3).any(|dots| dots == b"..."))
+        })
 }
 
 fn format_row(state: &mut State, node: Node) -> Result<()> {
     let mut cursor = node.walk();
     let mut first = true;
     let mut i: usize = 0;
-    let children: Vec<Node> = node.named_children(&mut cursor).collect();
+    let children: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|c| !state.is_soft_continuation(*c))
+        .collect();
     let cell_size = state.cell_size.clone();
     for (j, child) in children.iter().enumerate() {
         if !first && !child.is_extra() {
-            state.print(" ");
+            if cell_size.is_some() {
+                state.print(" ");
+            } else {
+                state.soft_line(" ");
+            }
         }
         let col_start = state.col;
         if let Some(cell_size) = &cell_size {
-            let negative = child.utf8_text(state.code)?.trim().starts_with('-');
+            let negative = state.text(*child)?.trim().starts_with('-');
             if i < cell_size.len() && cell_size[i].1 && !negative {
                 state.print(" ");
             }
-            format_node(state, *child)?;
+            format_cell(state, *child)?;
             if !child.is_extra() && i < cell_size.len() && j != children.len() - 1 {
                 let padding = (cell_size[i].0 + col_start).saturating_sub(state.col);
                 state.print(" ".repeat(padding).as_str());
             }
         } else {
-            format_node(state, *child)?;
+            format_cell(state, *child)?;
         }
         first = child.is_extra();
         if child.is_extra() {
@@ -637,70 +1290,143 @@ fn format_row(state: &mut State, node: Node) -> Result<()> {
 
 fn calculate_column_sizes(state: &mut State, node: Node) -> Result<()> {
     let mut cursor = node.walk();
-    let saved_formatted = state.formatted.clone();
-    let saved_inplace = state.arguments.inplace;
-    let saved_row = state.row;
-    let saved_col = state.col;
-    let saved_level = state.level;
-    let saved_extra_indent = state.extra_indentation;
-    state.level = 0;
-    state.extra_indentation = 0;
-    state.arguments.inplace = true;
-    state.formatted.clear();
-    state.col = 0;
-    let mut cell_size = vec![(0usize, false)];
-    let mut cell_text = vec![(String::new(), false)];
+    let mut cell_text: Vec<(String, bool)> = vec![];
     for row in node.named_children(&mut cursor).filter(|c| !c.is_extra()) {
         let mut cursor2 = row.walk();
         let mut i = 0;
         for cell in row.named_children(&mut cursor2) {
+            if state.is_soft_continuation(cell) {
+                continue;
+            }
             if cell.is_extra() {
                 i = 0;
                 continue;
             }
-            format_node(state, cell)?;
+            let measured = measure_cell(state, cell)?;
+            let (formatted, col) = (&measured.text, measured.col);
             if cell_text.len() > i {
-                if cell_text[i].0.len() < state.col
-                    || (cell_text[i].0.len() == state.col && !state.formatted.starts_with('-'))
-                {
+                let width = display_width(&cell_text[i].0);
+                if width < col || (width == col && !formatted.starts_with('-')) {
                     cell_text[i] = (
-                        state.formatted.trim().to_string(),
-                        cell_text[i].1 || state.formatted.trim().starts_with('-'),
+                        formatted.trim().to_string(),
+                        cell_text[i].1 || formatted.trim().starts_with('-'),
                     );
                 }
-                if state.formatted.starts_with('-') {
+                if formatted.starts_with('-') {
                     cell_text[i].1 = true;
                 }
             } else {
-                cell_text.push((state.formatted.clone(), state.formatted.starts_with('-')));
+                let negative = formatted.starts_with('-');
+                cell_text.push((formatted.clone(), negative));
             }
-            state.formatted.clear();
-            state.col = 0;
             i += 1;
         }
     }
-    cell_size.clear();
-    for (cell, neg) in cell_text {
-        let minus_offset = if cell.starts_with('-') { 0 } else { 1 };
-        cell_size.push((cell.len() + minus_offset, neg));
-    }
-    state.formatted = saved_formatted;
-    state.arguments.inplace = saved_inplace;
-    state.row = saved_row;
-    state.col = saved_col;
-    state.level = saved_level;
-    state.extra_indentation = saved_extra_indent;
+    let cell_size: Vec<(usize, bool)> = cell_text
+        .into_iter()
+        .map(|(cell, neg)| {
+            let minus_offset = if cell.starts_with('-') { 0 } else { 1 };
+            (display_width(&cell) + minus_offset, neg)
+        })
+        .collect();
     if !cell_size.is_empty() {
         state.cell_size = Some(cell_size);
     }
     Ok(())
 }
 
+/// Formats `cell` on its own, at the start of a line without indentation, to
+/// measure it. Each cell is measured only once, even when matrices nested in
+/// multi-line matrices are formatted again for every enclosing one.
+fn measure_cell<'s>(state: &'s mut State, cell: Node) -> Result<&'s MeasuredCell> {
+    if !state.measured.contains_key(&cell.id()) {
+        let saved_output = std::mem::take(&mut state.output);
+        // The cells are only measured here, nothing ends up in the output.
+        let saved_mappings = state.mappings.take();
+        let saved_row = state.row;
+        let saved_col = state.col;
+        let saved_level = state.level;
+        let saved_extra_indent = state.extra_indentation;
+        let saved_align = state.align.take();
+        let saved_measuring = std::mem::replace(&mut state.measuring, true);
+        state.level = 0;
+        state.extra_indentation = 0;
+        state.col = 0;
+        let result = format_node(state, cell);
+        let docs = std::mem::replace(&mut state.output, saved_output);
+        let col = state.col;
+        state.mappings = saved_mappings;
+        state.row = saved_row;
+        state.col = saved_col;
+        state.level = saved_level;
+        state.extra_indentation = saved_extra_indent;
+        state.align = saved_align;
+        state.measuring = saved_measuring;
+        result?;
+        let text = render(&docs, usize::MAX, 0).0;
+        let docs = (!text.contains('\n')).then_some(docs);
+        state
+            .measured
+            .insert(cell.id(), MeasuredCell { text, col, docs });
+    }
+    Ok(&state.measured[&cell.id()])
+}
+
+/// Formats a cell of a matrix in place, reusing the output from measuring it
+/// where possible.
+fn format_cell(state: &mut State, cell: Node) -> Result<()> {
+    // Reused output carries no source map marks.
+    if state.mappings.is_none() {
+        let at_indentation = state.col == 4 * state.level + state.extra_indentation;
+        let shifted = match state.measured.get(&cell.id()) {
+            Some(MeasuredCell {
+                docs: Some(docs),
+                text,
+                ..
+            }) => {
+                state.output.extend(docs.iter().cloned());
+                // Tabs in the cell move to other tab stops than when measured.
+                state.col = advance(state.col, text);
+                return Ok(());
+            }
+            // While measuring, a cell on several lines that starts at the
+            // indentation is laid out as it was measured, only further right.
+            // Formatting it again would format the cells nested in it again for
+            // every enclosing matrix.
+            Some(MeasuredCell {
+                text, docs: None, ..
+            }) if state.measuring && at_indentation => Some(shift_lines(text, state.col)),
+            _ => None,
+        };
+        if let Some(text) = shifted {
+            print_formatted(state, &text);
+            return Ok(());
+        }
+    }
+    format_node(state, cell)
+}
+
+/// Moves the lines of `text` after the first `by` columns to the right.
+fn shift_lines(text: &str, by: usize) -> String {
+    let margin = " ".repeat(by);
+    let mut shifted = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i != 0 {
+            shifted.push('\n');
+            if !line.is_empty() {
+                shifted.push_str(&margin);
+            }
+        }
+        shifted.push_str(line);
+    }
+    shifted
+}
+
 fn format_global(state: &mut State, node: Node) -> Result<()> {
     let mut cursor = node.walk();
     let children = node
         .children(&mut cursor)
-        .filter(|c| c.kind() != "line_continuation");
+        .filter(|c| !LineContinuation::can_cast(c.kind()));
     for (i, child) in children.enumerate() {
         if i != 0 {
             state.print(" ");
@@ -711,16 +1437,14 @@ fn format_global(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_while(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let condition = node.child_by_field_name("condition").err_at_loc(&node)?;
-    let body = node.children(&mut cursor).find(|c| c.kind() == "block");
+    let statement: WhileStatement = cast(node)?;
     state.print("while ");
-    format_node(state, condition)?;
+    format_node(state, statement.condition()?)?;
     print_linter_comment(state, node)?;
     state.println("");
     state.level += 1;
-    if let Some(body) = body {
-        format_block(state, body)?;
+    if let Some(body) = statement.block() {
+        format_node(state, body.syntax())?;
     } else {
         print_non_linter_comments(state, node)?;
     }
@@ -731,37 +1455,29 @@ fn format_while(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_try(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let body = node.children(&mut cursor).find(|c| c.kind() == "block");
-    let catch = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "catch_clause");
+    let statement: TryStatement = cast(node)?;
     state.println("try");
     state.level += 1;
-    if let Some(body) = body {
-        format_block(state, body)?;
+    if let Some(body) = statement.block() {
+        format_node(state, body.syntax())?;
     } else {
         print_non_linter_comments(state, node)?;
     }
     state.level -= 1;
-    if let Some(catch) = catch {
-        let catch_capture = catch
-            .children(&mut cursor)
-            .find(|c| c.kind() == "identifier");
-        let catch_body = catch.children(&mut cursor).find(|c| c.kind() == "block");
+    if let Some(catch) = statement.catch_clause() {
         state.indent();
         state.print("catch");
-        if let Some(capture) = catch_capture {
+        if let Some(capture) = catch.identifier() {
             state.print(" ");
             state.print_node(capture)?;
         }
-        print_linter_comment(state, catch)?;
+        print_linter_comment(state, catch.syntax())?;
         state.println("");
         state.level += 1;
-        if let Some(catch_body) = catch_body {
-            format_block(state, catch_body)?;
+        if let Some(catch_body) = catch.block() {
+            format_node(state, catch_body.syntax())?;
         } else {
-            print_non_linter_comments(state, catch)?;
+            print_non_linter_comments(state, catch.syntax())?;
         }
         state.level -= 1;
     }
@@ -771,47 +1487,34 @@ fn format_try(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_switch(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let condition = node.child_by_field_name("condition").err_at_loc(&node)?;
-    let cases: Vec<Node> = node
-        .named_children(&mut cursor)
-        .filter(|c| c.kind() == "case_clause")
-        .collect();
+    let statement: SwitchStatement = cast(node)?;
     state.print("switch ");
-    format_node(state, condition)?;
+    format_node(state, statement.condition()?)?;
     print_linter_comment(state, node)?;
     state.println("");
     state.level += 1;
-    for case in cases {
-        let condition = case.child_by_field_name("condition").err_at_loc(&case)?;
-        let block = case.children(&mut cursor).find(|c| c.kind() == "block");
+    for case in statement.cases() {
         state.indent();
         state.print("case ");
-        format_node(state, condition)?;
-        print_linter_comment(state, case)?;
+        format_node(state, case.condition()?)?;
+        print_linter_comment(state, case.syntax())?;
         state.println("");
         state.level += 1;
-        if let Some(block) = block {
-            format_block(state, block)?;
+        if let Some(block) = case.block() {
+            format_node(state, block.syntax())?;
         } else {
-            print_non_linter_comments(state, case)?;
+            print_non_linter_comments(state, case.syntax())?;
         }
         state.level -= 1;
     }
-    let otherwise = node
-        .named_children(&mut cursor)
-        .find(|c| c.kind() == "otherwise_clause");
-    if let Some(otherwise) = otherwise {
-        let block = otherwise
-            .children(&mut cursor)
-            .find(|c| c.kind() == "block");
+    if let Some(otherwise) = statement.otherwise() {
         state.indent();
         state.println("otherwise");
         state.level += 1;
-        if let Some(block) = block {
-            format_block(state, block)?;
+        if let Some(block) = otherwise.block() {
+            format_node(state, block.syntax())?;
         } else {
-            print_non_linter_comments(state, otherwise)?;
+            print_non_linter_comments(state, otherwise.syntax())?;
         }
         state.level -= 1;
     }
@@ -822,57 +1525,41 @@ fn format_switch(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_if(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let condition = node.child_by_field_name("condition").err_at_loc(&node)?;
-    let block = node.children(&mut cursor).find(|c| c.kind() == "block");
-    let elseif_clauses: Vec<Node> = node
-        .named_children(&mut cursor)
-        .filter(|c| c.kind() == "elseif_clause")
-        .collect();
-    let else_clause = node
-        .named_children(&mut cursor)
-        .find(|c| c.kind() == "else_clause");
+    let statement: IfStatement = cast(node)?;
     state.print("if ");
-    format_node(state, condition)?;
+    format_node(state, statement.condition()?)?;
     print_linter_comment(state, node)?;
     state.println("");
     state.level += 1;
-    if let Some(block) = block {
-        format_block(state, block)?;
+    if let Some(block) = statement.block() {
+        format_node(state, block.syntax())?;
     } else {
         print_non_linter_comments(state, node)?;
     }
     state.level -= 1;
-    for clause in elseif_clauses {
-        let condition = clause
-            .child_by_field_name("condition")
-            .err_at_loc(&clause)?;
-        let block = clause.children(&mut cursor).find(|c| c.kind() == "block");
+    for clause in statement.elseif_clauses() {
         state.indent();
         state.print("elseif ");
-        format_node(state, condition)?;
-        print_linter_comment(state, clause)?;
+        format_node(state, clause.condition()?)?;
+        print_linter_comment(state, clause.syntax())?;
         state.println("");
         state.level += 1;
         state.extra_indentation = 0;
-        if let Some(block) = block {
-            format_block(state, block)?;
+        if let Some(block) = clause.block() {
+            format_node(state, block.syntax())?;
         } else {
-            print_non_linter_comments_after(state, clause)?;
+            print_non_linter_comments_after(state, clause.syntax())?;
         }
         state.level -= 1;
     }
-    if let Some(else_clause) = else_clause {
-        let block = else_clause
-            .children(&mut cursor)
-            .find(|c| c.kind() == "block");
+    if let Some(else_clause) = statement.else_clause() {
         state.indent();
         state.println("else");
         state.level += 1;
-        if let Some(block) = block {
-            format_block(state, block)?;
+        if let Some(block) = else_clause.block() {
+            format_node(state, block.syntax())?;
         } else {
-            print_non_linter_comments_after(state, else_clause)?;
+            print_non_linter_comments_after(state, else_clause.syntax())?;
         }
         state.level -= 1;
     }
@@ -882,36 +1569,29 @@ fn format_if(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_for(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let parfor = node.child(0).err_at_loc(&node)?.utf8_text(state.code)?;
+    let statement: ForStatement = cast(node)?;
+    let parfor = state.text(statement.keyword()?)?;
     state.print(parfor);
     state.print(" ");
-    let iterator = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "iterator")
-        .err_at_loc(&node)?;
-    let block = node.children(&mut cursor).find(|c| c.kind() == "block");
-    let parfor_options = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "parfor_options");
-    if let Some(options) = parfor_options {
+    let iterator = statement.iterator()?;
+    if let Some(options) = statement.parfor_options() {
         state.print("(");
-        state.print_node(iterator.named_child(0).err_at_loc(&node)?)?;
+        state.print_node(iterator.variable()?)?;
         state.print(" = ");
-        format_node(state, iterator.named_child(1).err_at_loc(&node)?)?;
+        format_node(state, iterator.values()?)?;
         state.print(", ");
-        state.print_node(options.named_child(0).err_at_loc(&node)?)?;
+        state.print_node(options.value()?)?;
         state.print(")");
     } else {
-        state.print_node(iterator.named_child(0).err_at_loc(&node)?)?;
+        state.print_node(iterator.variable()?)?;
         state.print(" = ");
-        format_node(state, iterator.named_child(1).err_at_loc(&node)?)?;
+        format_node(state, iterator.values()?)?;
     }
     print_linter_comment(state, node)?;
     state.println("");
     state.level += 1;
-    if let Some(block) = block {
-        format_block(state, block)?;
+    if let Some(block) = statement.block() {
+        format_node(state, block.syntax())?;
     } else {
         print_non_linter_comments(state, node)?;
     }
@@ -922,45 +1602,53 @@ fn format_for(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_function(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let output = node
-        .named_children(&mut cursor)
-        .find(|n| n.kind() == "function_output");
-    let get_set = node
-        .children(&mut cursor)
-        .filter(|n| !n.is_named())
-        .find(|n| match n.utf8_text(state.code) {
-            Ok("get.") => true,
-            Ok("set.") => true,
-            Ok(_) => false,
-            Err(_) => false,
-        });
-    let name = node.child_by_field_name("name").err_at_loc(&node)?;
-    let arguments = node
-        .named_children(&mut cursor)
-        .find(|n| n.kind() == "function_arguments");
-    let argument_statements: Vec<Node> = node
-        .named_children(&mut cursor)
-        .filter(|n| n.kind() == "arguments_statement")
-        .collect();
-    let block = node
-        .named_children(&mut cursor)
-        .find(|n| n.kind() == "block");
+    let function: FunctionDefinition = cast(node)?;
     state.print("function ");
+    format_function_header(
+        state,
+        function.output(),
+        function.accessor_prefix(state.code),
+        function.name()?,
+        function.arguments(),
+    )?;
+    state.println("");
+    state.level += 1;
+    for argument_statement in function.arguments_statements() {
+        state.indent();
+        format_node(state, argument_statement.syntax())?;
+        state.println("");
+    }
+    if let Some(block) = function.block() {
+        format_node(state, block.syntax())?;
+    } else {
+        print_non_linter_comments(state, node)?;
+    }
+    state.level -= 1;
+    state.indent();
+    state.print("end");
+    Ok(())
+}
+
+/// Prints `[a, b] = get.name(x, y)`, the part shared by function definitions
+/// and signatures.
+fn format_function_header(
+    state: &mut State,
+    output: Option<FunctionOutput>,
+    accessor_prefix: Option<Node>,
+    name: Node,
+    arguments: Option<FunctionArguments>,
+) -> Result<()> {
     if let Some(output) = output {
-        format_node(state, output.child(0).err_at_loc(&node)?)?;
+        format_node(state, output.value()?)?;
         state.print(" = ");
     }
-    if let Some(get_set) = get_set {
-        state.print_node(get_set)?;
+    if let Some(prefix) = accessor_prefix {
+        state.print_node(prefix)?;
     }
     state.print_node(name)?;
     if let Some(arguments) = arguments {
         state.print("(");
-        let children = arguments
-            .named_children(&mut cursor)
-            .filter(|c| c.kind() != "line_continuation");
-        for (i, arg) in children.enumerate() {
+        for (i, arg) in arguments.arguments().enumerate() {
             if i != 0 {
                 state.print(", ");
             }
@@ -968,44 +1656,23 @@ fn format_function(state: &mut State, node: Node) -> Result<()> {
         }
         state.print(")");
     }
-    state.println("");
-    state.level += 1;
-    for argument_statement in argument_statements {
-        state.indent();
-        format_node(state, argument_statement)?;
-        state.println("");
-    }
-    if let Some(block) = block {
-        format_block(state, block)?;
-    } else {
-        print_non_linter_comments(state, node)?;
-    }
-    state.level -= 1;
-    state.indent();
-    state.print("end");
     Ok(())
 }
 
 fn format_arguments_statement(state: &mut State, node: Node) -> Result<()> {
+    let statement: ArgumentsStatement = cast(node)?;
     state.extra_indentation = 0;
-    let mut cursor = node.walk();
-    let attributes = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "attributes");
-    let properties = node
-        .children(&mut cursor)
-        .filter(|c| c.kind() == "property" || c.kind() == "class_property");
     state.print("arguments");
-    if let Some(attributes) = attributes {
+    if let Some(attributes) = statement.attributes() {
         state.print(" (");
-        format_arguments(state, attributes)?;
+        format_arguments(state, attributes.syntax())?;
         state.print(")");
     }
     state.println("");
     state.level += 1;
-    for property in properties {
+    for property in statement.properties() {
         state.indent();
-        format_node(state, property)?;
+        format_node(state, property.syntax())?;
         state.println("");
     }
     state.level -= 1;
@@ -1015,12 +1682,8 @@ fn format_arguments_statement(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_spmd(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let block = node.children(&mut cursor).find(|c| c.kind() == "block");
-    let worker_exprs: Vec<Node> = node
-        .named_children(&mut cursor)
-        .filter(|c| c.kind() != "block")
-        .collect();
+    let statement: SpmdStatement = cast(node)?;
+    let worker_exprs: Vec<Node> = statement.workers().collect();
     state.print("spmd");
     if !worker_exprs.is_empty() {
         state.print("(");
@@ -1035,8 +1698,8 @@ fn format_spmd(state: &mut State, node: Node) -> Result<()> {
     print_linter_comment(state, node)?;
     state.println("");
     state.level += 1;
-    if let Some(block) = block {
-        format_block(state, block)?;
+    if let Some(block) = statement.block() {
+        format_node(state, block.syntax())?;
     } else {
         print_non_linter_comments(state, node)?;
     }
@@ -1047,96 +1710,57 @@ fn format_spmd(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_property(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    for child in node.children(&mut cursor) {
-        if child.kind() == "@" {
-            return format_property_old(state, node);
-        }
+    let property: Property = cast(node)?;
+    if property.is_old_style() {
+        format_property_old(state, property)
+    } else {
+        format_property_new(state, property)
     }
-    format_property_new(state, node)
 }
 
-fn format_property_old(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let name = node.child_by_field_name("name").err_at_loc(&node)?;
-    let class = node
-        .children(&mut cursor)
-        .filter(|c| c.id() != name.id())
-        .find(|c| c.kind() == "identifier")
-        .err_at_loc(&node)?;
-    let kind = node
-        .children(&mut cursor)
-        .filter(|c| c.id() != name.id() && c.id() != class.id())
-        .find(|c| c.kind() == "identifier");
-    let default_value = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "default_value");
-    if name.kind() == "identifier" {
-        state.print_node(name)?;
-    } else {
-        format_property_name(state, name)?;
-    }
+fn format_property_old(state: &mut State, property: Property) -> Result<()> {
+    let node = property.syntax();
+    let class = property.class().err_at_loc(&node)?;
+    format_property_name(state, property.name()?)?;
     state.print("@");
     format_node(state, class)?;
-    if let Some(kind) = kind {
+    if let Some(kind) = property.old_style_kind() {
         state.print(" ");
         format_node(state, kind)?;
     }
-    if let Some(default_value) = default_value {
+    if let Some(default_value) = property.default_value() {
         state.print(" = ");
-        format_node(
-            state,
-            default_value.named_child(0).err_at_loc(&default_value)?,
-        )?;
+        format_node(state, default_value)?;
     }
     Ok(())
 }
 
-fn format_property_new(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let name = node.child_by_field_name("name").err_at_loc(&node)?;
-    let dimensions = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "dimensions");
-    let class = node
-        .children(&mut cursor)
-        .filter(|c| c.id() != name.id())
-        .find(|c| c.kind() == "identifier" || c.kind() == "property_name");
-    let validation_functions = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "validation_functions");
-    let default_value = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "default_value");
-    if name.kind() == "identifier" {
-        state.print_node(name)?;
-    } else {
-        format_property_name(state, name)?;
-    }
-    if let Some(dimmensions) = dimensions {
+fn format_property_new(state: &mut State, property: Property) -> Result<()> {
+    format_property_name(state, property.name()?)?;
+    if let Some(dimensions) = property.dimensions() {
         state.print(" ");
-        format_dimensions(state, dimmensions)?;
+        format_dimensions(state, dimensions)?;
     }
-    if let Some(class) = class {
+    if let Some(class) = property.class() {
         state.print(" ");
         format_node(state, class)?;
     }
-    if let Some(validation_functions) = validation_functions {
+    if let Some(validation_functions) = property.validation_functions() {
         state.print(" {");
         format_arguments(state, validation_functions)?;
         state.print("}");
     }
-    if let Some(default_value) = default_value {
+    if let Some(default_value) = property.default_value() {
         state.print(" = ");
-        format_node(
-            state,
-            default_value.named_child(0).err_at_loc(&default_value)?,
-        )?;
+        format_node(state, default_value)?;
     }
     Ok(())
 }
 
 fn format_property_name(state: &mut State, node: Node) -> Result<()> {
+    if node.kind() == "identifier" {
+        return state.print_node(node);
+    }
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
         state.print_node(child)?;
@@ -1144,10 +1768,9 @@ fn format_property_name(state: &mut State, node: Node) -> Result<()> {
     Ok(())
 }
 
-fn format_dimensions(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
+fn format_dimensions(state: &mut State, dimensions: Dimensions) -> Result<()> {
     state.print("(");
-    for (i, child) in node.named_children(&mut cursor).enumerate() {
+    for (i, child) in dimensions.dimensions().enumerate() {
         if i != 0 {
             state.print(",");
         }
@@ -1158,39 +1781,16 @@ fn format_dimensions(state: &mut State, node: Node) -> Result<()> {
 }
 
 fn format_classdef(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let attributes = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "attributes");
-    let name = node.child_by_field_name("name").err_at_loc(&node)?;
-    let superclasses = node
-        .children(&mut cursor)
-        .find(|c| c.kind() == "superclasses");
-    let properties: Vec<Node> = node
-        .children(&mut cursor)
-        .filter(|c| c.kind() == "properties")
-        .collect();
-    let methods: Vec<Node> = node
-        .children(&mut cursor)
-        .filter(|c| c.kind() == "methods")
-        .collect();
-    let events: Vec<Node> = node
-        .children(&mut cursor)
-        .filter(|c| c.kind() == "events")
-        .collect();
-    let enumerations: Vec<Node> = node
-        .children(&mut cursor)
-        .filter(|c| c.kind() == "enumeration")
-        .collect();
+    let class: ClassDefinition = cast(node)?;
     state.print("classdef ");
-    if let Some(attributes) = attributes {
+    if let Some(attributes) = class.attributes() {
         format_attributes(state, attributes)?;
         state.print(" ");
     }
-    state.print_node(name)?;
-    if let Some(superclasses) = superclasses {
+    state.print_node(class.name()?)?;
+    if let Some(superclasses) = class.superclasses() {
         state.print(" < ");
-        for (i, superclass) in superclasses.named_children(&mut cursor).enumerate() {
+        for (i, superclass) in superclasses.names().enumerate() {
             if i != 0 {
                 state.print(" & ");
             }
@@ -1200,24 +1800,24 @@ fn format_classdef(state: &mut State, node: Node) -> Result<()> {
     state.println("");
     state.extra_indentation = 0;
     state.level += 1;
-    for property in properties {
+    for properties in class.properties() {
         state.indent();
-        format_properties(state, property)?;
+        format_properties(state, properties)?;
         state.println("");
     }
-    for enumeration in enumerations {
+    for enumeration in class.enumerations() {
         state.indent();
         format_enum(state, enumeration)?;
         state.println("");
     }
-    for event in events {
+    for events in class.events() {
         state.indent();
-        format_events(state, event)?;
+        format_events(state, events)?;
         state.println("");
     }
-    for method in methods {
+    for methods in class.methods() {
         state.indent();
-        format_method(state, method)?;
+        format_method(state, methods)?;
         state.println("");
     }
     state.level -= 1;
@@ -1225,51 +1825,49 @@ fn format_classdef(state: &mut State, node: Node) -> Result<()> {
     Ok(())
 }
 
-fn format_attributes(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
+fn format_attributes(state: &mut State, attributes: Attributes) -> Result<()> {
     state.print("(");
-    let attributes = node
-        .children(&mut cursor)
-        .filter(|c| c.kind() == "attribute");
-    for (i, attr) in attributes.enumerate() {
+    for (i, attribute) in attributes.attributes().enumerate() {
         if i != 0 {
             state.print(", ");
         }
-        format_attribute(state, attr)?;
+        format_attribute(state, attribute)?;
     }
     state.print(")");
     Ok(())
 }
 
-fn format_attribute(state: &mut State, node: Node) -> Result<()> {
-    let identifier = node.named_child(0).err_at_loc(&node)?;
-    let value = node.named_child(1);
-    state.print_node(identifier)?;
-    if let Some(value) = value {
+fn format_attribute(state: &mut State, attribute: Attribute) -> Result<()> {
+    state.print_node(attribute.name()?)?;
+    if let Some(value) = attribute.value() {
         state.print("=");
         format_node(state, value)?;
     }
     Ok(())
 }
 
-fn format_properties(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let attributes = node
-        .named_children(&mut cursor)
-        .find(|c| c.kind() == "attributes");
-    let properties = node
-        .named_children(&mut cursor)
-        .filter(|c| c.kind() == "property");
-    state.print("properties");
+/// Prints ` (Name = value, ...)` and the line break after the keyword of a
+/// `properties`, `methods`, `events` or `enumeration` block.
+fn format_section_header(
+    state: &mut State,
+    keyword: &str,
+    attributes: Option<Attributes>,
+) -> Result<()> {
+    state.print(keyword);
     if let Some(attributes) = attributes {
         state.print(" ");
         format_attributes(state, attributes)?;
     }
     state.println("");
+    Ok(())
+}
+
+fn format_properties(state: &mut State, properties: Properties) -> Result<()> {
+    format_section_header(state, "properties", properties.attributes())?;
     state.level += 1;
-    for property in properties {
+    for property in properties.properties() {
         state.indent();
-        format_property(state, property)?;
+        format_property(state, property.syntax())?;
         state.println("");
     }
     state.level -= 1;
@@ -1278,38 +1876,21 @@ fn format_properties(state: &mut State, node: Node) -> Result<()> {
     Ok(())
 }
 
-fn format_enum(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let attributes = node
-        .named_children(&mut cursor)
-        .find(|c| c.kind() == "attributes");
-    let enums: Vec<Node> = node
-        .named_children(&mut cursor)
-        .filter(|c| c.kind() == "enum")
-        .collect();
-    state.print("enumeration");
-    if let Some(attributes) = attributes {
-        state.print(" ");
-        format_attributes(state, attributes)?;
-    }
-    state.println("");
+fn format_enum(state: &mut State, enumeration: Enumeration) -> Result<()> {
+    format_section_header(state, "enumeration", enumeration.attributes())?;
     state.level += 1;
-    for enumeration in enums {
+    for member in enumeration.members() {
         state.indent();
-        let mut parens = false;
-        for (i, c) in enumeration.named_children(&mut cursor).enumerate() {
-            if i == 0 {
-                state.print_node(c)?;
-            } else if i == 1 {
-                parens = true;
-                state.print(" (");
-                format_node(state, c)?;
-            } else {
-                state.print(", ");
-                format_node(state, c)?;
+        state.print_node(member.name()?)?;
+        let arguments: Vec<Node> = member.arguments().collect();
+        if !arguments.is_empty() {
+            state.print(" (");
+            for (i, argument) in arguments.into_iter().enumerate() {
+                if i != 0 {
+                    state.print(", ");
+                }
+                format_node(state, argument)?;
             }
-        }
-        if parens {
             state.print(")");
         }
         state.println("");
@@ -1320,22 +1901,10 @@ fn format_enum(state: &mut State, node: Node) -> Result<()> {
     Ok(())
 }
 
-fn format_events(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let attributes = node
-        .named_children(&mut cursor)
-        .find(|c| c.kind() == "attributes");
-    let identifiers = node
-        .named_children(&mut cursor)
-        .filter(|c| c.kind() == "identifier");
-    state.print("events");
-    if let Some(attributes) = attributes {
-        state.print(" ");
-        format_attributes(state, attributes)?;
-    }
-    state.println("");
+fn format_events(state: &mut State, events: Events) -> Result<()> {
+    format_section_header(state, "events", events.attributes())?;
     state.level += 1;
-    for identifier in identifiers {
+    for identifier in events.identifiers() {
         state.indent();
         state.print_node(identifier)?;
         state.println("");
@@ -1346,37 +1915,21 @@ fn format_events(state: &mut State, node: Node) -> Result<()> {
     Ok(())
 }
 
-fn format_method(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let attributes = node
-        .named_children(&mut cursor)
-        .find(|c| c.kind() == "attributes");
-    let definitions: Vec<Node> = node
-        .named_children(&mut cursor)
-        .filter(|c| c.kind() == "function_definition")
-        .collect();
-    let signatures: Vec<Node> = node
-        .named_children(&mut cursor)
-        .filter(|c| c.kind() == "function_signature")
-        .collect();
-    state.print("methods");
-    if let Some(attributes) = attributes {
-        state.print(" ");
-        format_attributes(state, attributes)?;
-    }
-    state.println("");
+fn format_method(state: &mut State, methods: Methods) -> Result<()> {
+    format_section_header(state, "methods", methods.attributes())?;
     state.level += 1;
+    let signatures: Vec<FunctionSignature> = methods.signatures().collect();
     for signature in &signatures {
         state.indent();
         format_signature(state, *signature)?;
         state.println("");
     }
-    for (i, definition) in definitions.iter().enumerate() {
+    for (i, definition) in methods.definitions().enumerate() {
         if i != 0 || !signatures.is_empty() {
             state.println("");
         }
         state.indent();
-        format_function(state, *definition)?;
+        format_function(state, definition.syntax())?;
         state.println("");
     }
     state.level -= 1;
@@ -1385,86 +1938,37 @@ fn format_method(state: &mut State, node: Node) -> Result<()> {
     Ok(())
 }
 
-fn format_signature(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let output = node
-        .named_children(&mut cursor)
-        .find(|n| n.kind() == "function_output");
-    let get_set = node
-        .children(&mut cursor)
-        .filter(|n| !n.is_named())
-        .find(|n| match n.utf8_text(state.code) {
-            Ok("get.") => true,
-            Ok("set.") => true,
-            Ok(_) => false,
-            Err(_) => false,
-        });
-    let name = node.child_by_field_name("name").err_at_loc(&node)?;
-    let arguments = node
-        .named_children(&mut cursor)
-        .find(|n| n.kind() == "function_arguments");
-    if let Some(output) = output {
-        format_node(state, output.child(0).err_at_loc(&output)?)?;
-        state.print(" = ");
-    }
-    if let Some(get_set) = get_set {
-        state.print_node(get_set)?;
-    }
-    state.print_node(name)?;
-    if let Some(arguments) = arguments {
-        state.print("(");
-        let children = arguments
-            .named_children(&mut cursor)
-            .filter(|c| c.kind() != "line_continuation");
-        for (i, arg) in children.enumerate() {
-            if i != 0 {
-                state.print(", ");
-            }
-            state.print_node(arg)?;
-        }
-        state.print(")");
-    }
-    Ok(())
+fn format_signature(state: &mut State, signature: FunctionSignature) -> Result<()> {
+    format_function_header(
+        state,
+        signature.output(),
+        signature.accessor_prefix(state.code),
+        signature.name()?,
+        signature.arguments(),
+    )
 }
 
 fn print_linter_comment(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let comments = node
-        .named_children(&mut cursor)
-        .filter(|n| n.kind() == "comment" && n.utf8_text(state.code).unwrap().starts_with("%#"));
-    for comment in comments {
-        format_comment(state, comment)?;
+    for comment in directives(node, state.code) {
+        format_node(state, comment.syntax())?;
     }
     Ok(())
 }
 
 fn print_non_linter_comments(state: &mut State, node: Node) -> Result<()> {
-    let mut cursor = node.walk();
-    let comments = node
-        .named_children(&mut cursor)
-        .filter(|n| n.kind() == "comment" && !n.utf8_text(state.code).unwrap().starts_with("%#"));
-    for comment in comments {
+    for comment in non_directive_comments(node, state.code) {
         state.indent();
-        format_comment(state, comment)?;
+        format_node(state, comment.syntax())?;
         state.println("");
     }
     Ok(())
 }
 
 fn print_non_linter_comments_after(state: &mut State, node: Node) -> Result<()> {
-    let mut comments: Vec<Node> = vec![];
-    let mut cur = node;
-    while let Some(next) = cur.next_named_sibling() {
-        if next.kind() == "comment" && !next.utf8_text(state.code).unwrap().starts_with("%#") {
-            comments.push(next);
-            cur = next;
-        } else {
-            break;
-        }
-    }
-    for comment in comments {
+    let code = state.code;
+    for comment in comments_after(node).take_while(|c| !c.is_directive(code)) {
         state.indent();
-        format_comment(state, comment)?;
+        format_node(state, comment.syntax())?;
         state.println("");
     }
     Ok(())
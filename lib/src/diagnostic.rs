@@ -0,0 +1,182 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::error::Span;
+use super::width::{advance, display_width};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tree_sitter::Node;
+
+const BLOCK_KEYWORDS: [&str; 10] = [
+    "classdef", "for", "function", "if", "parfor", "spmd", "switch", "try", "while", "methods",
+];
+
+/// A syntax problem found by the parser, with enough context to render it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// The file the code was read from, if known.
+    pub file: Option<String>,
+    pub span: Span,
+    /// The tree-sitter kind of the offending node: `ERROR`, or the kind of the
+    /// token the parser expected for `MISSING` nodes.
+    pub kind: String,
+    /// Whether the parser inserted a missing token instead of skipping text.
+    pub missing: bool,
+    pub message: String,
+    pub hint: Option<String>,
+    /// The full source line the span starts on.
+    pub line: String,
+}
+
+impl Diagnostic {
+    fn new(node: Node, code: &str) -> Self {
+        let text = node.utf8_text(code.as_bytes()).unwrap_or_default();
+        let (message, hint) = if node.is_missing() {
+            missing_message(node)
+        } else {
+            error_message(text)
+        };
+        let line = code
+            .lines()
+            .nth(node.start_position().row)
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            file: None,
+            span: Span::of(&node),
+            kind: node.kind().to_string(),
+            missing: node.is_missing(),
+            message,
+            hint,
+            line,
+        }
+    }
+
+    /// Renders the diagnostic in the style of a compiler error message:
+    ///
+    /// ```text
+    /// error: missing `end`
+    ///  --> script.m:3:5
+    ///   |
+    /// 3 |     if x > 1
+    ///   |     ^^^^^^^^
+    ///   = hint: unterminated block: missing `end`
+    /// ```
+    pub fn render(&self) -> String {
+        let location = format!(
+            "{}:{}",
+            self.file.as_deref().unwrap_or("<input>"),
+            self.span.start
+        );
+        let number = self.span.start.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let start = (self.span.start.column - 1).min(self.line.len());
+        let end = if self.span.end.line == self.span.start.line {
+            (self.span.end.column - 1).clamp(start, self.line.len())
+        } else {
+            self.line.len()
+        };
+        // Tabs are kept so the underline lines up with the excerpt, wide
+        // characters take as many spaces as they take columns.
+        let padding = self.line[..start]
+            .split('\t')
+            .map(|part| " ".repeat(display_width(part)))
+            .collect::<Vec<_>>()
+            .join("\t");
+        let col = display_width(&self.line[..start]);
+        let carets = "^".repeat((advance(col, &self.line[start..end]) - col).max(1));
+
+        let mut out = format!("error: {}\n", self.message);
+        out += &format!("{} --> {}\n", gutter, location);
+        out += &format!("{} |\n", gutter);
+        out += &format!("{} | {}\n", number, self.line);
+        out += &format!("{} | {}{}\n", gutter, padding, carets);
+        if let Some(hint) = &self.hint {
+            out += &format!("{} = hint: {}\n", gutter, hint);
+        }
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render())
+    }
+}
+
+/// Collects all `ERROR` and `MISSING` nodes below `root`, in document order.
+/// Errors nested inside an `ERROR` node are reported once, by their parent.
+pub(crate) fn error_nodes(root: Node) -> Vec<Node> {
+    let mut nodes = vec![];
+    let mut cursor = root.walk();
+    loop {
+        let node = cursor.node();
+        let is_error = node.is_error() || node.is_missing();
+        if is_error {
+            nodes.push(node);
+        }
+        if !is_error && node.has_error() && cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return nodes;
+            }
+        }
+    }
+}
+
+/// Builds a diagnostic for every syntax error in the tree rooted at `root`.
+pub(crate) fn collect(root: Node, code: &str) -> Vec<Diagnostic> {
+    error_nodes(root)
+        .into_iter()
+        .map(|node| Diagnostic::new(node, code))
+        .collect()
+}
+
+fn missing_message(node: Node) -> (String, Option<String>) {
+    let message = format!("missing `{}`", node.kind());
+    let hint = match node.kind() {
+        "end" => Some("unterminated block: missing `end`"),
+        ")" => Some("unclosed parenthesis: missing `)`"),
+        "]" => Some("unclosed matrix: missing `]`"),
+        "}" => Some("unclosed cell array: missing `}`"),
+        "'" | "\"" => Some("unterminated string"),
+        _ => None,
+    };
+    (message, hint.map(str::to_string))
+}
+
+fn error_message(text: &str) -> (String, Option<String>) {
+    let token = text.split_whitespace().next().unwrap_or_default();
+    let message = if token.is_empty() {
+        "syntax error".to_string()
+    } else {
+        format!("unexpected `{}`", token)
+    };
+
+    let count = |c: char| text.chars().filter(|&x| x == c).count();
+    let opens_block = text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| BLOCK_KEYWORDS.contains(&word));
+    let has_end = text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| word == "end");
+    let hint = if opens_block && !has_end {
+        Some("unterminated block: missing `end`")
+    } else if count('(') != count(')') {
+        Some("unbalanced parentheses")
+    } else if count('[') != count(']') {
+        Some("unbalanced brackets")
+    } else if count('{') != count('}') {
+        Some("unbalanced braces")
+    } else if count('"') % 2 == 1 {
+        Some("unterminated string")
+    } else {
+        None
+    };
+    (message, hint.map(str::to_string))
+}
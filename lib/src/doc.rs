@@ -0,0 +1,225 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::width::{advance, display_width};
+
+/// A document to be laid out against a maximum line width, in the style of
+/// Wadler's "prettier printer".
+///
+/// The formatter builds a document instead of printing text directly, and a
+/// layout pass then decides which line breaks to take:
+///
+/// ```
+/// use matlab_beautifier::Doc;
+///
+/// let doc = Doc::group(vec![
+///     Doc::text("f(a,"),
+///     Doc::align(vec![Doc::line(" ", ""), Doc::text("b)")]),
+/// ]);
+/// assert_eq!(doc.render(80), "f(a, b)");
+/// assert_eq!(doc.render(5), "f(a,\n    b)");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Doc {
+    /// Text printed as is. Lines started by line breaks in it are not
+    /// indented.
+    Text(String),
+    /// A line break that is always taken. The next line is indented like
+    /// broken lines, so not at all outside of indent and align nodes. A group
+    /// containing one is never flat.
+    HardLine,
+    /// A line break taken only if the enclosing group or fill does not fit.
+    /// `flat` is printed instead when it fits, `broken` right before the line
+    /// break.
+    Line {
+        flat: &'static str,
+        broken: &'static str,
+    },
+    /// Content whose line breaks are all taken, or none of them. Only the
+    /// content up to the first hard line break has to fit.
+    Group(Vec<Doc>),
+    /// Content whose line breaks are taken one by one, only where the content
+    /// up to the next one would not fit anymore.
+    Fill(Vec<Doc>),
+    /// Content whose broken lines are indented by some more columns.
+    Indent(usize, Vec<Doc>),
+    /// Content whose broken lines are indented to the column it starts at.
+    Align(Vec<Doc>),
+    /// Records the output offset it ends up at, see [`Doc::render_marked`].
+    Mark(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+    Fill,
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn line(flat: &'static str, broken: &'static str) -> Self {
+        Self::Line { flat, broken }
+    }
+
+    pub fn group(docs: Vec<Doc>) -> Self {
+        Self::Group(docs)
+    }
+
+    pub fn fill(docs: Vec<Doc>) -> Self {
+        Self::Fill(docs)
+    }
+
+    pub fn indent(width: usize, docs: Vec<Doc>) -> Self {
+        Self::Indent(width, docs)
+    }
+
+    pub fn align(docs: Vec<Doc>) -> Self {
+        Self::Align(docs)
+    }
+
+    /// Lays the document out so that lines are at most `width` columns long
+    /// wherever possible. Columns count displayed characters, with wide East
+    /// Asian characters taking two and tabs moving to multiples of four.
+    pub fn render(&self, width: usize) -> String {
+        render(std::slice::from_ref(self), width, 0).0
+    }
+
+    /// Like [`Doc::render`], also returning the output offset of every
+    /// [`Doc::Mark`], indexed by its number.
+    pub fn render_marked(&self, width: usize) -> (String, Vec<usize>) {
+        render(std::slice::from_ref(self), width, 0)
+    }
+}
+
+/// Lays out a sequence of documents printed from column `col` on, see
+/// [`Doc::render_marked`].
+pub(crate) fn render(docs: &[Doc], width: usize, col: usize) -> (String, Vec<usize>) {
+    let mut out = String::new();
+    let mut marks = vec![];
+    let mut col = col;
+    // The indentation of the line started by the last hard line break, added
+    // once something is printed on it so that empty lines stay empty.
+    let mut pending = 0;
+    // Documents still to print, last one first, with their indentation.
+    let mut stack: Vec<(usize, Mode, &Doc)> =
+        docs.iter().rev().map(|doc| (0, Mode::Break, doc)).collect();
+    while let Some((indent, mode, doc)) = stack.pop() {
+        if matches!(doc, Doc::Text(_) | Doc::Line { .. } | Doc::Mark(_)) {
+            out.extend(std::iter::repeat_n(' ', pending));
+            pending = 0;
+        }
+        match doc {
+            Doc::Text(text) => {
+                out += text;
+                col = match text.rfind('\n') {
+                    Some(i) => display_width(&text[i + 1..]),
+                    None => advance(col, text),
+                };
+            }
+            Doc::HardLine => {
+                out.push('\n');
+                pending = indent;
+                col = indent;
+            }
+            Doc::Line { flat, .. }
+                if mode == Mode::Flat
+                    || mode == Mode::Fill
+                        && fits(&[], &stack, width.saturating_sub(col + flat.len())) =>
+            {
+                out += flat;
+                col += flat.len();
+            }
+            Doc::Line { broken, .. } => {
+                out += broken;
+                out.push('\n');
+                out.extend(std::iter::repeat_n(' ', indent));
+                col = indent;
+            }
+            Doc::Group(docs) => {
+                let fits = mode == Mode::Flat || fits(docs, &stack, width.saturating_sub(col));
+                let mode = if fits { Mode::Flat } else { Mode::Break };
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+            Doc::Fill(docs) => {
+                let mode = if mode == Mode::Flat {
+                    Mode::Flat
+                } else {
+                    Mode::Fill
+                };
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+            Doc::Indent(width, docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (indent + width, mode, doc)));
+            }
+            Doc::Align(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (col, mode, doc)));
+            }
+            Doc::Mark(id) => {
+                if marks.len() <= *id {
+                    marks.resize(id + 1, 0);
+                }
+                marks[*id] = out.len();
+            }
+        }
+    }
+    (out, marks)
+}
+
+/// Whether `docs` fit flat into `width` columns, together with whatever
+/// follows them up to the next line break that may be taken. Groups and fills
+/// coming up next have to fit flat as a whole.
+///
+/// Whatever follows a forced line break starts on a fresh line, so the break
+/// ends what has to fit. In `docs` themselves it cannot be flat, though.
+fn fits(docs: &[Doc], rest: &[(usize, Mode, &Doc)], width: usize) -> bool {
+    let mut remaining = width;
+    // Documents still to measure, with whether they are part of `docs`.
+    let mut stack: Vec<(bool, Mode, &Doc)> = docs
+        .iter()
+        .rev()
+        .map(|doc| (true, Mode::Flat, doc))
+        .collect();
+    let mut rest = rest.iter().rev();
+    loop {
+        let Some((own, mode, doc)) = stack
+            .pop()
+            .or_else(|| rest.next().map(|&(_, m, d)| (false, m, d)))
+        else {
+            return true;
+        };
+        let printed = match doc {
+            Doc::Text(text) => match text.find('\n') {
+                Some(_) if own => return false,
+                Some(i) => return display_width(&text[..i]) <= remaining,
+                None => display_width(text),
+            },
+            Doc::HardLine => return !own,
+            Doc::Line { flat, .. } if mode == Mode::Flat => flat.len(),
+            Doc::Line { broken, .. } => return broken.len() <= remaining,
+            Doc::Group(docs) | Doc::Fill(docs) if mode == Mode::Fill => {
+                stack.extend(docs.iter().rev().map(|doc| (own, Mode::Flat, doc)));
+                0
+            }
+            Doc::Fill(docs) if mode == Mode::Break => {
+                stack.extend(docs.iter().rev().map(|doc| (own, Mode::Fill, doc)));
+                0
+            }
+            Doc::Group(docs) | Doc::Fill(docs) | Doc::Indent(_, docs) | Doc::Align(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (own, mode, doc)));
+                0
+            }
+            Doc::Mark(_) => 0,
+        };
+        if printed > remaining {
+            return false;
+        }
+        remaining -= printed;
+    }
+}
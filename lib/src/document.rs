@@ -0,0 +1,202 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::beautifier::format_tree_cached;
+use super::doc::Doc;
+use super::error::{FormatError, Result};
+use super::formatter::parse_limited;
+use super::limits::{check_input_size, Limits};
+use super::options::FormatOptions;
+use std::collections::HashMap;
+use std::ops::Range;
+use tree_sitter::Tree;
+pub use tree_sitter::{InputEdit, Point};
+
+/// A document that is edited and formatted over and over, e.g. in an editor.
+///
+/// The document keeps the syntax tree and the formatted output of every
+/// top-level statement around. After an edit only the changed parts are
+/// re-parsed, and only the statements touched by the edit or whose subtrees
+/// changed, as told by [`Tree::changed_ranges`], are formatted again. The
+/// result is always the same as formatting the whole source.
+///
+/// ```
+/// use matlab_beautifier::{Document, FormatOptions};
+///
+/// let mut document = Document::new("x=1;\ny=2;\n", FormatOptions::default())?;
+/// document.replace(5..8, "y=3")?;
+/// assert_eq!(document.format()?, "x = 1;\ny = 3;\n");
+/// # Ok::<(), matlab_beautifier::FormatError>(())
+/// ```
+pub struct Document {
+    options: FormatOptions,
+    source: String,
+    tree: Tree,
+    cache: StatementCache,
+}
+
+impl Document {
+    /// Fails if the source exceeds the limits set in the options, like every
+    /// edit that makes it do so.
+    pub fn new(source: impl Into<String>, options: FormatOptions) -> Result<Self> {
+        let source = source.into();
+        check_input_size(source.len(), &options)?;
+        let tree = parse_limited(&source, None, &options, None)?;
+        Ok(Self {
+            options,
+            source,
+            tree,
+            cache: StatementCache::default(),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    /// Applies an edit described in tree-sitter terms. `new_source` is the
+    /// whole source after the edit. The document is left as it was if this
+    /// fails.
+    pub fn edit(&mut self, edit: &InputEdit, new_source: impl Into<String>) -> Result<()> {
+        let source = new_source.into();
+        check_input_size(source.len(), &self.options)?;
+        let mut old_tree = self.tree.clone();
+        old_tree.edit(edit);
+        let tree = parse_limited(&source, Some(&old_tree), &self.options, None)?;
+        let changed: Vec<Range<usize>> = old_tree
+            .changed_ranges(&tree)
+            .map(|range| range.start_byte..range.end_byte)
+            .collect();
+        self.cache.edit(edit);
+        self.cache.invalidate(&changed);
+        self.source = source;
+        self.tree = tree;
+        Ok(())
+    }
+
+    /// Replaces the bytes in `range` by `text`. Fails with
+    /// [`FormatError::InvalidRange`] if `range` is not within the source or
+    /// splits a character.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) -> Result<()> {
+        if range.start > range.end
+            || !self.source.is_char_boundary(range.start)
+            || !self.source.is_char_boundary(range.end)
+        {
+            return Err(FormatError::InvalidRange { range });
+        }
+        let mut source = self.source.clone();
+        source.replace_range(range.clone(), text);
+        let new_end = range.start + text.len();
+        let edit = InputEdit {
+            start_byte: range.start,
+            old_end_byte: range.end,
+            new_end_byte: new_end,
+            start_position: point(&self.source, range.start),
+            old_end_position: point(&self.source, range.end),
+            new_end_position: point(&source, new_end),
+        };
+        self.edit(&edit, source)
+    }
+
+    /// Formats the current source, reusing the output of every top-level
+    /// statement that did not change since the last call.
+    pub fn format(&mut self) -> Result<String> {
+        self.cache.start_generation();
+        let limits = Limits::start(&self.options, None);
+        let formatted = format_tree_cached(
+            &self.source,
+            &self.tree,
+            &self.options,
+            &mut self.cache,
+            &limits,
+        );
+        formatted.map(|formatted| formatted.text)
+    }
+}
+
+/// The formatted output of top-level statements, keyed by their place in the
+/// source and where they start in the output.
+#[derive(Debug, Default)]
+pub(crate) struct StatementCache {
+    previous: HashMap<CacheKey, CachedStatement>,
+    current: HashMap<CacheKey, CachedStatement>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    pub(crate) range: Range<usize>,
+    pub(crate) level: usize,
+    pub(crate) col: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct CachedStatement {
+    pub(crate) docs: Vec<Doc>,
+    /// The column the statement ends at.
+    pub(crate) col: usize,
+    /// The number of lines the statement spans.
+    pub(crate) rows: usize,
+}
+
+impl StatementCache {
+    /// Forgets the statements that were not used by the last format.
+    fn start_generation(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    pub(crate) fn get(&mut self, key: &CacheKey) -> Option<&CachedStatement> {
+        if !self.current.contains_key(key) {
+            let (key, output) = self.previous.remove_entry(key)?;
+            self.current.insert(key, output);
+        }
+        self.current.get(key)
+    }
+
+    pub(crate) fn insert(&mut self, key: CacheKey, output: CachedStatement) {
+        self.current.insert(key, output);
+    }
+
+    /// Moves the statements after `edit` to where they are now and forgets
+    /// the ones it touches.
+    fn edit(&mut self, edit: &InputEdit) {
+        self.previous.clear();
+        self.current = std::mem::take(&mut self.current)
+            .into_iter()
+            .filter_map(|(mut key, output)| {
+                if key.range.end < edit.start_byte {
+                    Some((key, output))
+                } else if key.range.start > edit.old_end_byte {
+                    let start = key.range.start - edit.old_end_byte + edit.new_end_byte;
+                    key.range = start..start + key.range.len();
+                    Some((key, output))
+                } else {
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// Forgets the statements touching any of the `changed` byte ranges.
+    fn invalidate(&mut self, changed: &[Range<usize>]) {
+        self.current.retain(|key, _| {
+            !changed
+                .iter()
+                .any(|range| key.range.start <= range.end && range.start <= key.range.end)
+        });
+    }
+}
+
+/// The tree-sitter point of a byte offset.
+fn point(code: &str, offset: usize) -> Point {
+    let before = &code[..offset];
+    let row = before.matches('\n').count();
+    let column = before.rfind('\n').map_or(offset, |i| offset - i - 1);
+    Point { row, column }
+}
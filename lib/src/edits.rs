@@ -0,0 +1,184 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::error::{FormatError, Result};
+use super::formatter::Formatter;
+use super::options::FormatOptions;
+use serde::{Deserialize, Serialize};
+use similar::{Algorithm, DiffTag, TextDiff};
+use std::ops::Range;
+use std::time::Duration;
+
+/// Hunks longer than this are replaced as a whole instead of being refined
+/// character by character.
+const MAX_REFINED_HUNK: usize = 16 * 1024;
+
+/// A position in the input, both as a byte offset and as a 0-based line and
+/// UTF-16 column, as used by the language server protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct TextPosition {
+    pub offset: usize,
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TextRange {
+    pub start: TextPosition,
+    pub end: TextPosition,
+}
+
+/// Replaces `range` of the input by `new_text`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub range: TextRange,
+    pub new_text: String,
+}
+
+/// Formats `code` and returns the edits turning it into the formatted code.
+pub fn text_edits(code: &str, options: &FormatOptions) -> Result<Vec<TextEdit>> {
+    Formatter::new(options.clone()).format_edits(code)
+}
+
+impl Formatter {
+    /// Formats `code` and returns the edits turning it into the formatted
+    /// code. See [`text_edits`].
+    pub fn format_edits(&self, code: &str) -> Result<Vec<TextEdit>> {
+        let formatted = self.format_str(code)?;
+        Ok(compute_edits(code, &formatted))
+    }
+}
+
+/// Computes small, non-overlapping edits turning `old` into `new`, sorted by
+/// position.
+pub fn compute_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let index = LineIndex::new(old);
+    let lines = TextDiff::configure()
+        .algorithm(Algorithm::Myers)
+        .diff_lines(old, new);
+    let old_offsets = offsets(lines.old_slices());
+    let new_offsets = offsets(lines.new_slices());
+
+    let mut edits = vec![];
+    for op in lines.ops() {
+        let (tag, old_lines, new_lines) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            continue;
+        }
+        let old_range = old_offsets[old_lines.start]..old_offsets[old_lines.end];
+        let new_range = new_offsets[new_lines.start]..new_offsets[new_lines.end];
+        for (old_range, new_range) in refine(old, new, old_range, new_range) {
+            edits.push(TextEdit {
+                range: TextRange {
+                    start: index.position(old_range.start),
+                    end: index.position(old_range.end),
+                },
+                new_text: new[new_range].to_string(),
+            });
+        }
+    }
+    edits
+}
+
+/// Applies edits as returned by [`compute_edits`] to `code`. Fails with
+/// [`FormatError::InvalidRange`] if an edit overlaps the one before it, is
+/// not within `code` or splits a character.
+pub fn apply_edits(code: &str, edits: &[TextEdit]) -> Result<String> {
+    let mut result = String::with_capacity(code.len());
+    let mut copied = 0;
+    for edit in edits {
+        let range = edit.range.start.offset..edit.range.end.offset;
+        if range.start < copied
+            || range.start > range.end
+            || !code.is_char_boundary(range.start)
+            || !code.is_char_boundary(range.end)
+        {
+            return Err(FormatError::InvalidRange { range });
+        }
+        result += &code[copied..range.start];
+        result += &edit.new_text;
+        copied = range.end;
+    }
+    result += &code[copied..];
+    Ok(result)
+}
+
+/// Splits a changed hunk into the character ranges that actually differ.
+fn refine(
+    old: &str,
+    new: &str,
+    old_range: Range<usize>,
+    new_range: Range<usize>,
+) -> Vec<(Range<usize>, Range<usize>)> {
+    let (old_hunk, new_hunk) = (&old[old_range.clone()], &new[new_range.clone()]);
+    if old_hunk.len() + new_hunk.len() > MAX_REFINED_HUNK {
+        return vec![(old_range, new_range)];
+    }
+    let chars = TextDiff::configure()
+        .algorithm(Algorithm::Myers)
+        .timeout(Duration::from_millis(100))
+        .diff_chars(old_hunk, new_hunk);
+    let old_offsets = offsets(chars.old_slices());
+    let new_offsets = offsets(chars.new_slices());
+
+    // Adjacent deletions and insertions are merged into a single replacement.
+    let mut ranges: Vec<(Range<usize>, Range<usize>)> = vec![];
+    for op in chars.ops() {
+        let (tag, old_chars, new_chars) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            continue;
+        }
+        let o = old_range.start + old_offsets[old_chars.start]
+            ..old_range.start + old_offsets[old_chars.end];
+        let n = new_range.start + new_offsets[new_chars.start]
+            ..new_range.start + new_offsets[new_chars.end];
+        match ranges.last_mut() {
+            Some((last_old, last_new)) if last_old.end == o.start && last_new.end == n.start => {
+                last_old.end = o.end;
+                last_new.end = n.end;
+            }
+            _ => ranges.push((o, n)),
+        }
+    }
+    ranges
+}
+
+/// The byte offset at which each token starts, plus the total length.
+fn offsets(slices: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(slices.len() + 1);
+    let mut offset = 0;
+    offsets.push(0);
+    for slice in slices {
+        offset += slice.len();
+        offsets.push(offset);
+    }
+    offsets
+}
+
+/// Converts byte offsets into line/UTF-16 column positions.
+struct LineIndex<'a> {
+    code: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(code: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { code, line_starts }
+    }
+
+    fn position(&self, offset: usize) -> TextPosition {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        TextPosition {
+            offset,
+            line,
+            character: self.code[start..offset].encode_utf16().count(),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::diagnostic::Diagnostic;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
+use tree_sitter::{Node, Point};
+
+pub(crate) type Result<T, E = FormatError> = std::result::Result<T, E>;
+
+/// A 1-based line/column position in the source code. Columns count bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<Point> for Position {
+    fn from(point: Point) -> Self {
+        Self {
+            line: point.row + 1,
+            column: point.column + 1,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A region of source code, from `start` (inclusive) to `end` (exclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub(crate) fn of(node: &Node) -> Self {
+        Self {
+            start: node.start_position().into(),
+            end: node.end_position().into(),
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Everything that can go wrong while formatting.
+///
+/// Variants caused by a syntax node carry its span and tree-sitter kind.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FormatError {
+    /// The code does not parse. `span` and `kind` describe the first error,
+    /// `diagnostics` lists every error found in the file.
+    #[error("syntax error at {span} ({kind})")]
+    Syntax {
+        span: Span,
+        kind: &'static str,
+        diagnostics: Vec<Diagnostic>,
+    },
+
+    /// The code parses, but the formatter cannot reproduce it faithfully.
+    #[error("unsupported {kind} at {span}: {reason}")]
+    UnsupportedConstruct {
+        span: Span,
+        kind: &'static str,
+        reason: &'static str,
+    },
+
+    /// The tree did not have the shape the formatter expected. This is a bug
+    /// in the formatter, or a grammar change it does not know about yet.
+    #[error("internal error at {span} ({kind}): {message}")]
+    Internal {
+        span: Span,
+        kind: &'static str,
+        message: &'static str,
+    },
+
+    /// Tree-sitter could not be set up or gave up parsing.
+    #[error("parser error: {0}")]
+    Parser(String),
+
+    /// Verification found that the formatted code does not mean the same as
+    /// the input. `span` and `kind` describe the first input node that
+    /// differs, `output` is where the output differs.
+    #[error("formatting changed the meaning of the code at {span} ({kind}), output at {output}")]
+    NotEquivalent {
+        span: Span,
+        kind: &'static str,
+        output: Span,
+    },
+
+    /// Formatting the output once more changed it. `diff` lists the lines
+    /// that changed, see [`line_diff`].
+    ///
+    /// [`line_diff`]: super::line_diff
+    #[error("formatting is not idempotent:\n{diff}")]
+    NotIdempotent { diff: String },
+
+    /// The code given to [`format_expression`] is not a single expression.
+    /// `span` and `kind` describe what was found instead.
+    ///
+    /// [`format_expression`]: super::format_expression
+    #[error("expected a single expression, found {kind} at {span}")]
+    NotAnExpression { span: Span, kind: &'static str },
+
+    /// The code is nested more than `limit` levels deep, see
+    /// [`FormatOptions::with_max_depth`]. `span` and `kind` describe the node
+    /// at the limit.
+    ///
+    /// [`FormatOptions::with_max_depth`]: super::FormatOptions::with_max_depth
+    #[error("{kind} at {span} is nested more than {limit} levels deep")]
+    TooDeep {
+        span: Span,
+        kind: &'static str,
+        limit: usize,
+    },
+
+    /// The code is longer than [`FormatOptions::with_max_input_size`] allows.
+    ///
+    /// [`FormatOptions::with_max_input_size`]: super::FormatOptions::with_max_input_size
+    #[error("input is larger than {limit} bytes")]
+    InputTooLarge { limit: usize },
+
+    /// Parsing took longer than [`FormatOptions::with_parse_timeout`] allows.
+    ///
+    /// [`FormatOptions::with_parse_timeout`]: super::FormatOptions::with_parse_timeout
+    #[error("parsing took longer than {limit:?}")]
+    ParseTimeout { limit: Duration },
+
+    /// Formatting took longer than [`FormatOptions::with_format_timeout`]
+    /// allows.
+    ///
+    /// [`FormatOptions::with_format_timeout`]: super::FormatOptions::with_format_timeout
+    #[error("formatting took longer than {limit:?}")]
+    FormatTimeout { limit: Duration },
+
+    /// Formatting was aborted through a [`CancellationToken`].
+    ///
+    /// [`CancellationToken`]: super::CancellationToken
+    #[error("formatting was cancelled")]
+    Cancelled,
+
+    /// A byte range into the code is reversed, out of bounds or splits a
+    /// character.
+    #[error("invalid range {range:?}")]
+    InvalidRange { range: Range<usize> },
+
+    /// The text of a node is not valid UTF-8.
+    #[error("invalid UTF-8 at {span} ({kind})")]
+    Encoding { span: Span, kind: &'static str },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl FormatError {
+    pub(crate) fn internal(node: &Node, message: &'static str) -> Self {
+        Self::Internal {
+            span: Span::of(node),
+            kind: node.kind(),
+            message,
+        }
+    }
+
+    /// Records the file the code was read from in the diagnostics.
+    pub(crate) fn with_file(mut self, file: &str) -> Self {
+        if let Self::Syntax { diagnostics, .. } = &mut self {
+            for diagnostic in diagnostics {
+                diagnostic.file = Some(file.to_string());
+            }
+        }
+        self
+    }
+
+    /// The syntax diagnostics carried by the error, if any.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            Self::Syntax { diagnostics, .. } => diagnostics,
+            _ => &[],
+        }
+    }
+
+    /// The location the error refers to, if any.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Syntax { span, .. }
+            | Self::UnsupportedConstruct { span, .. }
+            | Self::Internal { span, .. }
+            | Self::NotEquivalent { span, .. }
+            | Self::NotAnExpression { span, .. }
+            | Self::TooDeep { span, .. }
+            | Self::Encoding { span, .. } => Some(*span),
+            Self::NotIdempotent { .. }
+            | Self::InputTooLarge { .. }
+            | Self::ParseTimeout { .. }
+            | Self::FormatTimeout { .. }
+            | Self::Cancelled
+            | Self::InvalidRange { .. }
+            | Self::Parser(_)
+            | Self::Io(_) => None,
+        }
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! The C interface, declared in `include/matlab_beautifier.h`.
+//!
+//! Everything returned to C is owned by the `mb_result` it is stored in and
+//! released with `mb_result_free`. Panics never cross the boundary, they are
+//! reported as `MB_STATUS_PANIC`.
+//!
+//! Panics can only be caught when they unwind, and the `release` profile
+//! aborts on them instead. Build the shared library with the `release-ffi`
+//! profile, as in `cargo build -p matlab_beautifier --profile release-ffi`.
+
+use super::error::{FormatError, Span};
+use super::formatter::Formatter;
+use super::options::FormatOptions;
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// The outcome of a call to `mb_format`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MbStatus {
+    Ok = 0,
+    /// A pointer that must not be NULL was NULL.
+    InvalidArgument,
+    /// The code is not valid UTF-8.
+    InvalidUtf8,
+    /// The code does not parse.
+    SyntaxError,
+    /// Formatting failed for another reason, see `mb_error_message`.
+    Error,
+    /// The formatter panicked. This is a bug in the formatter.
+    Panic,
+}
+
+/// Options controlling how code is formatted, see `FormatOptions`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MbOptions {
+    pub sparse_math: bool,
+    pub sparse_add: bool,
+    pub tolerant: bool,
+    /// The column statements are wrapped at, 0 to never wrap them.
+    pub max_width: usize,
+    pub verify: bool,
+    pub ensure_idempotent: bool,
+}
+
+impl From<&MbOptions> for FormatOptions {
+    fn from(options: &MbOptions) -> Self {
+        FormatOptions::new()
+            .with_sparse_math(options.sparse_math)
+            .with_sparse_add(options.sparse_add)
+            .with_tolerant(options.tolerant)
+            .with_max_width((options.max_width != 0).then_some(options.max_width))
+            .with_verify(options.verify)
+            .with_ensure_idempotent(options.ensure_idempotent)
+    }
+}
+
+/// Why formatting failed, read through the `mb_error_*` functions.
+pub struct MbError {
+    message: CString,
+    span: Option<Span>,
+}
+
+/// The output of `mb_format`, to be released with `mb_result_free`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct MbResult {
+    /// The formatted code, NUL-terminated, or NULL if formatting failed.
+    pub text: *mut c_char,
+    /// The length of `text` in bytes, without the NUL.
+    pub len: usize,
+    /// Why formatting failed, or NULL if it succeeded.
+    pub error: *mut MbError,
+}
+
+/// The default options: dense math operators everywhere, no wrapping.
+#[no_mangle]
+pub extern "C" fn mb_options_default() -> MbOptions {
+    MbOptions {
+        sparse_math: false,
+        sparse_add: false,
+        tolerant: false,
+        max_width: 0,
+        verify: false,
+        ensure_idempotent: false,
+    }
+}
+
+/// Formats the `len` bytes of UTF-8 code at `code` into `result`.
+///
+/// `options` may be NULL to use the defaults. `result` is overwritten without
+/// being released first and must be released with `mb_result_free`, also
+/// when formatting failed.
+///
+/// # Safety
+///
+/// `code` must point to `len` readable bytes, `options` must be NULL or point
+/// to valid options and `result` must point to writable memory for an
+/// `mb_result`.
+#[no_mangle]
+pub unsafe extern "C" fn mb_format(
+    code: *const c_char,
+    len: usize,
+    options: *const MbOptions,
+    result: *mut MbResult,
+) -> MbStatus {
+    if result.is_null() {
+        return MbStatus::InvalidArgument;
+    }
+    result.write(MbResult {
+        text: ptr::null_mut(),
+        len: 0,
+        error: ptr::null_mut(),
+    });
+    let result = &mut *result;
+    if code.is_null() && len != 0 {
+        return fail(result, MbStatus::InvalidArgument, "code is NULL", None);
+    }
+    let code = if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(code.cast::<u8>(), len)
+    };
+    let options = match options.as_ref() {
+        Some(options) => FormatOptions::from(options),
+        None => FormatOptions::default(),
+    };
+    let Ok(code) = std::str::from_utf8(code) else {
+        return fail(result, MbStatus::InvalidUtf8, "code is not valid UTF-8", None);
+    };
+    let formatted = catch_unwind(AssertUnwindSafe(|| {
+        Formatter::new(options).format_str(code)
+    }));
+    match formatted {
+        Ok(Ok(text)) => match CString::new(text) {
+            Ok(text) => {
+                result.len = text.as_bytes().len();
+                result.text = text.into_raw();
+                MbStatus::Ok
+            }
+            Err(_) => fail(result, MbStatus::Error, "code contains a NUL byte", None),
+        },
+        Ok(Err(err)) => {
+            let status = match err {
+                FormatError::Syntax { .. } => MbStatus::SyntaxError,
+                FormatError::Encoding { .. } => MbStatus::InvalidUtf8,
+                _ => MbStatus::Error,
+            };
+            fail(result, status, &err.to_string(), err.span())
+        }
+        Err(_) => fail(result, MbStatus::Panic, "the formatter panicked", None),
+    }
+}
+
+fn fail(result: &mut MbResult, status: MbStatus, message: &str, span: Option<Span>) -> MbStatus {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    result.error = Box::into_raw(Box::new(MbError { message, span }));
+    status
+}
+
+/// Releases what `mb_format` stored in `result` and resets it. Does nothing
+/// if `result` is NULL.
+///
+/// # Safety
+///
+/// `result` must be NULL or have been filled in by `mb_format`, and not be
+/// released already.
+#[no_mangle]
+pub unsafe extern "C" fn mb_result_free(result: *mut MbResult) {
+    let Some(result) = result.as_mut() else {
+        return;
+    };
+    if !result.text.is_null() {
+        drop(CString::from_raw(result.text));
+    }
+    if !result.error.is_null() {
+        drop(Box::from_raw(result.error));
+    }
+    result.text = ptr::null_mut();
+    result.len = 0;
+    result.error = ptr::null_mut();
+}
+
+/// The message describing why formatting failed, or NULL if it succeeded.
+/// The message lives as long as `result` is not released.
+///
+/// # Safety
+///
+/// `result` must be NULL or have been filled in by `mb_format`.
+#[no_mangle]
+pub unsafe extern "C" fn mb_error_message(result: *const MbResult) -> *const c_char {
+    match error(result) {
+        Some(error) => error.message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The 1-based line where formatting failed, or 0 if unknown.
+///
+/// # Safety
+///
+/// `result` must be NULL or have been filled in by `mb_format`.
+#[no_mangle]
+pub unsafe extern "C" fn mb_error_line(result: *const MbResult) -> usize {
+    error(result)
+        .and_then(|error| error.span)
+        .map_or(0, |span| span.start.line)
+}
+
+/// The 1-based column, in bytes, where formatting failed, or 0 if unknown.
+///
+/// # Safety
+///
+/// `result` must be NULL or have been filled in by `mb_format`.
+#[no_mangle]
+pub unsafe extern "C" fn mb_error_column(result: *const MbResult) -> usize {
+    error(result)
+        .and_then(|error| error.span)
+        .map_or(0, |span| span.start.column)
+}
+
+unsafe fn error<'r>(result: *const MbResult) -> Option<&'r MbError> {
+    result.as_ref().and_then(|result| result.error.as_ref())
+}
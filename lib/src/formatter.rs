@@ -0,0 +1,187 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::beautifier::{format_tree, Formatted};
+use super::encoding::read_to_string;
+use super::error::{FormatError, Result};
+use super::limits::{check_input_size, CancellationToken, Limits};
+use super::options::FormatOptions;
+use super::registry::FormatterRegistry;
+use std::cell::RefCell;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tree_sitter::{ParseOptions, ParseState, Parser, Tree};
+
+thread_local! {
+    // Parsers are not Sync, so each thread keeps its own one around for reuse.
+    static PARSER: RefCell<Option<Parser>> = const { RefCell::new(None) };
+}
+
+/// A reusable formatter holding a set of options.
+///
+/// The formatter is `Send + Sync` and can be shared between threads. Parsers
+/// are created lazily and reused per thread, so formatting many files through
+/// the same formatter avoids setting up tree-sitter over and over.
+#[derive(Debug, Clone, Default)]
+pub struct Formatter {
+    options: FormatOptions,
+    /// Only set when not using the built-in formatters.
+    registry: Option<Arc<FormatterRegistry>>,
+    cancellation: Option<CancellationToken>,
+}
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Formatter>();
+};
+
+impl Formatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Self {
+            options,
+            registry: None,
+            cancellation: None,
+        }
+    }
+
+    /// Formats nodes with the formatters in `registry` instead of the
+    /// built-in ones.
+    pub fn with_registry(mut self, registry: FormatterRegistry) -> Self {
+        self.registry = Some(Arc::new(registry));
+        self
+    }
+
+    /// Aborts formatting with [`FormatError::Cancelled`] once `token` is
+    /// cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    pub(crate) fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    pub fn registry(&self) -> &FormatterRegistry {
+        self.registry
+            .as_deref()
+            .unwrap_or_else(|| FormatterRegistry::builtin())
+    }
+
+    /// Formats a string containing MATLAB code.
+    pub fn format_str(&self, code: &str) -> Result<String> {
+        self.format(code).map(|formatted| formatted.text)
+    }
+
+    /// Formats a string containing MATLAB code, also reporting what was left
+    /// untouched in tolerant mode.
+    ///
+    /// Fails when the code or the time it takes exceed the limits set in the
+    /// options, or when cancelled, see [`Formatter::with_cancellation`].
+    pub fn format(&self, code: &str) -> Result<Formatted> {
+        check_input_size(code.len(), &self.options)?;
+        self.format_within_size(code)
+    }
+
+    fn format_within_size(&self, code: &str) -> Result<Formatted> {
+        let cancellation = self.cancellation.as_ref();
+        let tree = parse_limited(code, None, &self.options, cancellation)?;
+        let limits = Limits::start(&self.options, cancellation);
+        format_tree(code, &tree, &self.options, self.registry(), &limits)
+    }
+
+    /// Reads all of `reader`, detecting its encoding, and formats it.
+    pub fn format_reader<R: Read>(&self, reader: R) -> Result<String> {
+        // Reading one byte more than allowed tells whether there is too much.
+        let limit = self
+            .options
+            .max_input_size()
+            .map_or(u64::MAX, |limit| (limit as u64).saturating_add(1));
+        let mut bytes = vec![];
+        reader.take(limit).read_to_end(&mut bytes)?;
+        check_input_size(bytes.len(), &self.options)?;
+        let code = read_to_string(&mut bytes.as_slice(), None)?.0 + "\n";
+        self.format_within_size(&code)
+            .map(|formatted| formatted.text)
+    }
+
+    /// Reads the file at `path`, detecting its encoding, and formats it.
+    /// Syntax diagnostics refer to the file by `path`.
+    pub fn format_file<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)?;
+        self.format_reader(file)
+            .map_err(|err| err.with_file(&path.display().to_string()))
+    }
+}
+
+/// Parses `code` with this thread's parser, creating it on first use.
+pub(crate) fn parse(code: &str) -> Result<Tree> {
+    with_parser(|parser| parser.parse(code, None))?.ok_or_else(could_not_parse)
+}
+
+/// Parses `code`, giving up once `cancellation` is cancelled or parsing took
+/// longer than the timeout of `options`. Reuses the unchanged parts of
+/// `old_tree`, which must have been edited to match `code` already.
+pub(crate) fn parse_limited(
+    code: &str,
+    old_tree: Option<&Tree>,
+    options: &FormatOptions,
+    cancellation: Option<&CancellationToken>,
+) -> Result<Tree> {
+    let deadline = options
+        .parse_timeout()
+        .map(|timeout| Instant::now() + timeout);
+    let cancelled = || cancellation.is_some_and(CancellationToken::is_cancelled);
+    let timed_out = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+    let mut progress = |_: &ParseState| cancelled() || timed_out();
+    let tree = with_parser(|parser| {
+        let mut read = |i: usize, _| code.as_bytes().get(i..).unwrap_or_default();
+        let parse_options = ParseOptions::new().progress_callback(&mut progress);
+        let tree = parser.parse_with_options(&mut read, old_tree, Some(parse_options));
+        if tree.is_none() {
+            // Otherwise the next parse would resume where this one stopped.
+            parser.reset();
+        }
+        tree
+    })?;
+    tree.ok_or_else(|| match options.parse_timeout() {
+        _ if cancelled() => FormatError::Cancelled,
+        Some(limit) if timed_out() => FormatError::ParseTimeout { limit },
+        _ => could_not_parse(),
+    })
+}
+
+/// Runs `parse` with this thread's parser, creating it on first use.
+fn with_parser<T>(parse: impl FnOnce(&mut Parser) -> T) -> Result<T> {
+    // The parser is taken out of the slot while in use, so a nested call (e.g.
+    // re-parsing the formatted output) simply gets a fresh one.
+    let mut parser = match PARSER.take() {
+        Some(parser) => parser,
+        None => new_parser()?,
+    };
+    let result = parse(&mut parser);
+    PARSER.set(Some(parser));
+    Ok(result)
+}
+
+fn could_not_parse() -> FormatError {
+    FormatError::Parser("could not parse file".to_string())
+}
+
+fn new_parser() -> Result<Parser> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_matlab::LANGUAGE.into())
+        .map_err(|err| FormatError::Parser(err.to_string()))?;
+    Ok(parser)
+}
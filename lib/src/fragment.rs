@@ -0,0 +1,168 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::beautifier::{check_syntax, format_fragment_tree};
+use super::error::{FormatError, Result, Span};
+use super::formatter::{parse, parse_limited, Formatter};
+use super::idempotency::check_idempotent;
+use super::limits::{check_input_size, CancellationToken, Limits};
+use super::options::FormatOptions;
+use super::registry::FormatterRegistry;
+use super::verify;
+use tree_sitter::Tree;
+
+/// Nodes at the root of a file that are not expressions, see
+/// [`format_expression`].
+const NON_EXPRESSIONS: [&str; 17] = [
+    "arguments_statement",
+    "assignment",
+    "break_statement",
+    "class_definition",
+    "command",
+    "comment",
+    "continue_statement",
+    "for_statement",
+    "function_definition",
+    "global_operator",
+    "if_statement",
+    "persistent_operator",
+    "return_statement",
+    "spmd_statement",
+    "switch_statement",
+    "try_statement",
+    "while_statement",
+];
+
+/// Formats `code`, a list of statements or a single expression, as if it
+/// were nested `base_indent` levels deep, e.g. to splice it into a function.
+///
+/// Every line is indented by `base_indent` levels. Unlike whole files, the
+/// last statement keeps the separator it was written with, so no `;` is
+/// added and none is taken away, and there is no final newline. Use
+/// [`FormatOptions::with_terminate_fragments`] to end fragments like files.
+///
+/// ```
+/// use matlab_beautifier::{format_fragment, FormatOptions};
+///
+/// let options = FormatOptions::new();
+/// let formatted = format_fragment("x=1;\nif x\ny=x+1\nend", 1, &options)?;
+/// assert_eq!(formatted, "    x = 1;\n    if x\n        y = x+1;\n    end");
+/// assert_eq!(format_fragment("foo( a,b )", 0, &options)?, "foo(a, b)");
+/// # Ok::<(), matlab_beautifier::FormatError>(())
+/// ```
+pub fn format_fragment(code: &str, base_indent: usize, options: &FormatOptions) -> Result<String> {
+    format_fragment_with(
+        code,
+        base_indent,
+        options,
+        FormatterRegistry::builtin(),
+        None,
+    )
+}
+
+/// Formats `code`, which must be a single expression, without a `;` or a
+/// newline after it.
+///
+/// ```
+/// use matlab_beautifier::{format_expression, FormatError, FormatOptions};
+///
+/// let options = FormatOptions::new();
+/// assert_eq!(format_expression("[1,2;3,4]'", &options)?, "[1 2; 3 4]'");
+/// assert!(matches!(
+///     format_expression("x = 1", &options),
+///     Err(FormatError::NotAnExpression { kind: "assignment", .. })
+/// ));
+/// # Ok::<(), FormatError>(())
+/// ```
+pub fn format_expression(code: &str, options: &FormatOptions) -> Result<String> {
+    format_expression_with(code, options, FormatterRegistry::builtin(), None)
+}
+
+fn format_expression_with(
+    code: &str,
+    options: &FormatOptions,
+    registry: &FormatterRegistry,
+    cancellation: Option<&CancellationToken>,
+) -> Result<String> {
+    check_input_size(code.len(), options)?;
+    let tree = parse_limited(code, None, options, cancellation)?;
+    let limits = Limits::start(options, cancellation);
+    let root = tree.root_node();
+    check_syntax(root, code, options)?;
+    let mut cursor = root.walk();
+    let mut children = root.named_children(&mut cursor);
+    match (children.next(), children.next()) {
+        (Some(expression), None) if !NON_EXPRESSIONS.contains(&expression.kind()) => {}
+        (Some(_), Some(node)) | (Some(node), None) => {
+            return Err(FormatError::NotAnExpression {
+                span: Span::of(&node),
+                kind: node.kind(),
+            })
+        }
+        (None, _) => {
+            return Err(FormatError::NotAnExpression {
+                span: Span::of(&root),
+                kind: "nothing",
+            })
+        }
+    }
+    let options = options.clone().with_terminate_fragments(false);
+    format_parsed_fragment(code, &tree, 0, &options, registry, &limits)
+}
+
+fn format_fragment_with(
+    code: &str,
+    base_indent: usize,
+    options: &FormatOptions,
+    registry: &FormatterRegistry,
+    cancellation: Option<&CancellationToken>,
+) -> Result<String> {
+    check_input_size(code.len(), options)?;
+    let tree = parse_limited(code, None, options, cancellation)?;
+    let limits = Limits::start(options, cancellation);
+    format_parsed_fragment(code, &tree, base_indent, options, registry, &limits)
+}
+
+fn format_parsed_fragment(
+    code: &str,
+    tree: &Tree,
+    base_indent: usize,
+    options: &FormatOptions,
+    registry: &FormatterRegistry,
+    limits: &Limits,
+) -> Result<String> {
+    let text = format_fragment_tree(code, tree, base_indent, options, registry, limits)?;
+    if options.verify() {
+        let output = parse(&text)?;
+        verify::compare(tree.root_node(), code, output.root_node(), &text)?;
+    }
+    if options.ensure_idempotent() {
+        check_idempotent(&text, options, |tree, options| {
+            format_fragment_tree(&text, tree, base_indent, options, registry, limits)
+        })?;
+    }
+    Ok(text)
+}
+
+impl Formatter {
+    /// Formats `code` as if it were nested `base_indent` levels deep. See
+    /// [`format_fragment`].
+    pub fn format_fragment(&self, code: &str, base_indent: usize) -> Result<String> {
+        format_fragment_with(
+            code,
+            base_indent,
+            self.options(),
+            self.registry(),
+            self.cancellation(),
+        )
+    }
+
+    /// Formats `code`, which must be a single expression. See
+    /// [`format_expression`].
+    pub fn format_expression(&self, code: &str) -> Result<String> {
+        format_expression_with(code, self.options(), self.registry(), self.cancellation())
+    }
+}
@@ -0,0 +1,61 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::error::{FormatError, Result};
+use super::formatter::parse;
+use super::options::FormatOptions;
+use tree_sitter::Tree;
+
+/// Lists the lines that differ between `expected` and `got`, one pair of
+/// lines each, to make differences between two outputs easy to spot.
+///
+/// ```
+/// use matlab_beautifier::line_diff;
+///
+/// let diff = line_diff("x = 1;\ny = 2;\n", "x = 1;\ny=2;\n");
+/// assert_eq!(
+///     diff,
+///     "  line   2 expected: \"y = 2;\"\n  line   2      got: \"y=2;\"\n"
+/// );
+/// ```
+pub fn line_diff(expected: &str, got: &str) -> String {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let got_lines: Vec<&str> = got.lines().collect();
+    let mut diff = String::new();
+    let max = expected_lines.len().max(got_lines.len());
+    for i in 0..max {
+        let exp = expected_lines.get(i).copied().unwrap_or("<missing>");
+        let got = got_lines.get(i).copied().unwrap_or("<missing>");
+        if exp != got {
+            diff.push_str(&format!("  line {:>3} expected: {:?}\n", i + 1, exp));
+            diff.push_str(&format!("  line {:>3}      got: {:?}\n", i + 1, got));
+        }
+    }
+    diff
+}
+
+/// Formats `formatted` once more with `format` and fails if that changes
+/// anything. `format` gets the tree of `formatted` and `options` without the
+/// checks and the source map.
+pub(crate) fn check_idempotent(
+    formatted: &str,
+    options: &FormatOptions,
+    format: impl FnOnce(&Tree, &FormatOptions) -> Result<String>,
+) -> Result<()> {
+    let options = options
+        .clone()
+        .with_ensure_idempotent(false)
+        .with_verify(false)
+        .with_source_map(false);
+    let tree = parse(formatted)?;
+    let again = format(&tree, &options)?;
+    if again != formatted {
+        return Err(FormatError::NotIdempotent {
+            diff: line_diff(formatted, &again),
+        });
+    }
+    Ok(())
+}
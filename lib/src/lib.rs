@@ -4,8 +4,40 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-mod args;
+pub mod ast;
 mod beautifier;
+mod diagnostic;
+mod doc;
+mod document;
+mod edits;
+mod encoding;
+mod error;
+mod ffi;
+mod formatter;
+mod fragment;
+mod idempotency;
+mod limits;
+mod options;
+mod range;
+mod registry;
+mod source_map;
+mod verify;
+mod visit;
+mod width;
 
 pub use beautifier::*;
-pub use args::*;
+pub use diagnostic::*;
+pub use doc::*;
+pub use document::*;
+pub use edits::*;
+pub use error::*;
+pub use formatter::*;
+pub use fragment::*;
+pub use idempotency::*;
+pub use limits::*;
+pub use options::*;
+pub use range::*;
+pub use registry::*;
+pub use source_map::*;
+pub use verify::*;
+pub use visit::*;
@@ -0,0 +1,96 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::error::{FormatError, Result};
+use super::options::FormatOptions;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Aborts formatting from another thread, e.g. when an editor request became
+/// stale. See [`Formatter::with_cancellation`].
+///
+/// Clones share the same flag, so the token can be handed to the formatter
+/// and kept around to cancel it.
+///
+/// ```
+/// use matlab_beautifier::{CancellationToken, FormatError, Formatter};
+///
+/// let token = CancellationToken::new();
+/// let formatter = Formatter::default().with_cancellation(token.clone());
+/// token.cancel();
+/// assert!(matches!(
+///     formatter.format_str("x = 1;\n"),
+///     Err(FormatError::Cancelled)
+/// ));
+/// ```
+///
+/// [`Formatter::with_cancellation`]: super::Formatter::with_cancellation
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes formatting with this token fail with [`FormatError::Cancelled`]
+    /// as soon as it notices. There is no way back.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`CancellationToken::cancel`] was called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// When formatting has to stop, checked between statements.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Limits {
+    timeout: Option<(Instant, Duration)>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl Limits {
+    /// Starts the formatting timeout of `options`.
+    pub(crate) fn start(options: &FormatOptions, cancellation: Option<&CancellationToken>) -> Self {
+        Self {
+            timeout: options
+                .format_timeout()
+                .map(|timeout| (Instant::now() + timeout, timeout)),
+            cancellation: cancellation.cloned(),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Fails if formatting was cancelled or took too long.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(FormatError::Cancelled);
+        }
+        match self.timeout {
+            Some((deadline, limit)) if Instant::now() >= deadline => {
+                Err(FormatError::FormatTimeout { limit })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Fails if `size` bytes of code are more than `options` allow.
+pub(crate) fn check_input_size(size: usize, options: &FormatOptions) -> Result<()> {
+    match options.max_input_size() {
+        Some(limit) if size > limit => Err(FormatError::InputTooLarge { limit }),
+        _ => Ok(()),
+    }
+}
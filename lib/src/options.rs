@@ -0,0 +1,225 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Options controlling how code is formatted.
+///
+/// The options are immutable once built. Use the `with_*` methods to derive a
+/// new set of options from an existing one:
+///
+/// ```
+/// use matlab_beautifier::FormatOptions;
+///
+/// let options = FormatOptions::new().with_sparse_add(true);
+/// assert!(options.sparse_add());
+/// assert!(!options.sparse_math());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FormatOptions {
+    sparse_math: bool,
+    sparse_add: bool,
+    tolerant: bool,
+    source_map: bool,
+    max_width: Option<usize>,
+    verify: bool,
+    ensure_idempotent: bool,
+    terminate_fragments: bool,
+    max_depth: usize,
+    max_input_size: Option<usize>,
+    parse_timeout: Option<Duration>,
+    format_timeout: Option<Duration>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            sparse_math: false,
+            sparse_add: false,
+            tolerant: false,
+            source_map: false,
+            max_width: None,
+            verify: false,
+            ensure_idempotent: false,
+            terminate_fragments: false,
+            max_depth: 256,
+            max_input_size: None,
+            parse_timeout: None,
+            format_timeout: None,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Creates the default options: dense math operators everywhere.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prints spaces around all math operators.
+    pub fn with_sparse_math(mut self, sparse_math: bool) -> Self {
+        self.sparse_math = sparse_math;
+        self
+    }
+
+    /// Prints spaces around addition/subtraction operators only.
+    pub fn with_sparse_add(mut self, sparse_add: bool) -> Self {
+        self.sparse_add = sparse_add;
+        self
+    }
+
+    /// Formats files containing syntax errors instead of refusing them. The
+    /// smallest statement around each error is copied through unchanged.
+    pub fn with_tolerant(mut self, tolerant: bool) -> Self {
+        self.tolerant = tolerant;
+        self
+    }
+
+    /// Records which output each input node was formatted into, so positions
+    /// can be mapped from the input to the output. See [`SourceMap`].
+    ///
+    /// [`SourceMap`]: super::SourceMap
+    pub fn with_source_map(mut self, source_map: bool) -> Self {
+        self.source_map = source_map;
+        self
+    }
+
+    /// Wraps statements longer than `max_width` columns with `...`
+    /// continuations, at the lowest-precedence operator, after the commas of
+    /// argument lists or between matrix and cell elements. Lines that cannot
+    /// be broken there are left long.
+    pub fn with_max_width(mut self, max_width: Option<usize>) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Parses the formatted code again and checks that it means the same as
+    /// the input, failing instead of returning code that changed meaning. See
+    /// [`verify_equivalent`].
+    ///
+    /// [`verify_equivalent`]: super::verify_equivalent
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Formats the output once more and fails with a line diff if that
+    /// changes anything, instead of returning output that would keep drifting.
+    pub fn with_ensure_idempotent(mut self, ensure_idempotent: bool) -> Self {
+        self.ensure_idempotent = ensure_idempotent;
+        self
+    }
+
+    /// Ends formatted fragments like whole files, with a `;` after the last
+    /// statement and a final newline. See [`format_fragment`].
+    ///
+    /// [`format_fragment`]: super::format_fragment
+    pub fn with_terminate_fragments(mut self, terminate_fragments: bool) -> Self {
+        self.terminate_fragments = terminate_fragments;
+        self
+    }
+
+    /// Fails with [`FormatError::TooDeep`] instead of formatting syntax nested
+    /// more than `max_depth` levels deep, which could overflow the stack.
+    /// Chains of operators with the same precedence, like `a + b + c`, count
+    /// as a single level however long they are. Defaults to 256.
+    ///
+    /// [`FormatError::TooDeep`]: super::FormatError::TooDeep
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Fails with [`FormatError::InputTooLarge`] instead of formatting more
+    /// than `max_input_size` bytes of code.
+    ///
+    /// [`FormatError::InputTooLarge`]: super::FormatError::InputTooLarge
+    pub fn with_max_input_size(mut self, max_input_size: Option<usize>) -> Self {
+        self.max_input_size = max_input_size;
+        self
+    }
+
+    /// Fails with [`FormatError::ParseTimeout`] when parsing takes longer than
+    /// `parse_timeout`.
+    ///
+    /// [`FormatError::ParseTimeout`]: super::FormatError::ParseTimeout
+    pub fn with_parse_timeout(mut self, parse_timeout: Option<Duration>) -> Self {
+        self.parse_timeout = parse_timeout;
+        self
+    }
+
+    /// Fails with [`FormatError::FormatTimeout`] when formatting the parsed
+    /// code takes longer than `format_timeout`. The time is checked between
+    /// statements, so a single huge statement can take longer.
+    ///
+    /// [`FormatError::FormatTimeout`]: super::FormatError::FormatTimeout
+    pub fn with_format_timeout(mut self, format_timeout: Option<Duration>) -> Self {
+        self.format_timeout = format_timeout;
+        self
+    }
+
+    /// Whether spaces are printed around all math operators.
+    pub fn sparse_math(&self) -> bool {
+        self.sparse_math
+    }
+
+    /// Whether spaces are printed around addition/subtraction operators.
+    pub fn sparse_add(&self) -> bool {
+        self.sparse_add
+    }
+
+    /// Whether files containing syntax errors are formatted around the errors.
+    pub fn tolerant(&self) -> bool {
+        self.tolerant
+    }
+
+    /// Whether a source map is recorded while formatting.
+    pub fn source_map(&self) -> bool {
+        self.source_map
+    }
+
+    /// The column statements are wrapped at, if any.
+    pub fn max_width(&self) -> Option<usize> {
+        self.max_width
+    }
+
+    /// Whether the formatted code is checked to mean the same as the input.
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+
+    /// Whether the output is checked to stay the same when formatted again.
+    pub fn ensure_idempotent(&self) -> bool {
+        self.ensure_idempotent
+    }
+
+    /// Whether fragments end with a `;` and a newline like whole files.
+    pub fn terminate_fragments(&self) -> bool {
+        self.terminate_fragments
+    }
+
+    /// How deep syntax may be nested to be formatted.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// How many bytes of code may be formatted, if limited.
+    pub fn max_input_size(&self) -> Option<usize> {
+        self.max_input_size
+    }
+
+    /// How long parsing may take, if limited.
+    pub fn parse_timeout(&self) -> Option<Duration> {
+        self.parse_timeout
+    }
+
+    /// How long formatting the parsed code may take, if limited.
+    pub fn format_timeout(&self) -> Option<Duration> {
+        self.format_timeout
+    }
+}
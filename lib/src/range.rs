@@ -0,0 +1,260 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::beautifier::{check_syntax, format_statement, is_terminated, statement_separator};
+use super::error::Result;
+use super::formatter::{parse_limited, Formatter};
+use super::limits::{check_input_size, CancellationToken, Limits};
+use super::options::FormatOptions;
+use super::registry::FormatterRegistry;
+use super::width::display_width;
+use std::ops::{Range, RangeInclusive};
+use tree_sitter::Node;
+
+/// Nodes that indent the statements nested in them by one level.
+const INDENTING: [&str; 5] = [
+    "block",
+    "case_clause",
+    "class_definition",
+    "methods",
+    "otherwise_clause",
+];
+
+/// Nodes whose blocks may be formatted on their own.
+const CLAUSES: [&str; 6] = [
+    "case_clause",
+    "catch_clause",
+    "else_clause",
+    "elseif_clause",
+    "function_definition",
+    "otherwise_clause",
+];
+
+/// A part of the input to format.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FormatRange {
+    /// 1-based, inclusive line numbers.
+    Lines(RangeInclusive<usize>),
+    /// Byte offsets into the input.
+    Bytes(Range<usize>),
+}
+
+impl FormatRange {
+    fn to_bytes(&self, code: &str) -> Range<usize> {
+        match self {
+            Self::Bytes(range) => range.start.min(code.len())..range.end.min(code.len()),
+            Self::Lines(lines) => {
+                let start = line_start(code, lines.start().saturating_sub(1));
+                let end = line_start(code, *lines.end());
+                let end = code[..end].strip_suffix('\n').map_or(end, str::len);
+                start..end.max(start)
+            }
+        }
+    }
+}
+
+/// Formats only the statements of `code` overlapping `ranges`, leaving
+/// everything else untouched.
+///
+/// Statements are formatted at the indentation level of the blocks they are
+/// nested in. A range that touches the header or the `end` of a compound
+/// statement formats the whole statement.
+pub fn format_range(code: &str, ranges: &[FormatRange], options: &FormatOptions) -> Result<String> {
+    format_range_with(code, ranges, options, FormatterRegistry::builtin(), None)
+}
+
+fn format_range_with(
+    code: &str,
+    ranges: &[FormatRange],
+    options: &FormatOptions,
+    registry: &FormatterRegistry,
+    cancellation: Option<&CancellationToken>,
+) -> Result<String> {
+    check_input_size(code.len(), options)?;
+    let tree = parse_limited(code, None, options, cancellation)?;
+    let limits = Limits::start(options, cancellation);
+    let root = tree.root_node();
+    check_syntax(root, code, options)?;
+
+    let mut selected: Vec<(Node, usize)> = vec![];
+    for range in ranges {
+        select(root, code, &range.to_bytes(code), &mut selected);
+    }
+    selected.sort_by_key(|(node, _)| (node.start_byte(), std::cmp::Reverse(node.end_byte())));
+    // Drop statements nested in other selected statements.
+    let mut end = 0;
+    selected.retain(|(node, _)| {
+        let keep = node.start_byte() >= end;
+        if keep {
+            end = node.end_byte();
+        }
+        keep
+    });
+
+    let mut formatted = String::with_capacity(code.len());
+    let mut copied = 0;
+    for (node, level) in selected {
+        limits.check()?;
+        let line_start = code[..node.start_byte()].rfind('\n').map_or(0, |i| i + 1);
+        let at_line_start = code[line_start..node.start_byte()].trim().is_empty();
+        let start = if at_line_start {
+            line_start
+        } else {
+            node.start_byte()
+        };
+        let end = match statement_separator(node) {
+            Some(separator) if is_terminated(node) => separator.end_byte(),
+            _ => node.end_byte(),
+        };
+        formatted += &code[copied..start];
+        // A statement further along a line starts where the output before it
+        // ends, but never left of the indentation of its block.
+        let col = (!at_line_start).then(|| {
+            let line = formatted.rfind('\n').map_or(0, |i| i + 1);
+            display_width(&formatted[line..]).max(4 * level)
+        });
+        let text = format_statement(code, node, level, col, options, registry, &limits)?;
+        formatted += if at_line_start {
+            &text
+        } else {
+            text.trim_start()
+        };
+        copied = end;
+    }
+    formatted += &code[copied..];
+    Ok(formatted)
+}
+
+impl Formatter {
+    /// Formats only the statements of `code` overlapping `ranges`. See
+    /// [`format_range`].
+    pub fn format_range(&self, code: &str, ranges: &[FormatRange]) -> Result<String> {
+        format_range_with(
+            code,
+            ranges,
+            self.options(),
+            self.registry(),
+            self.cancellation(),
+        )
+    }
+}
+
+/// Selects the innermost statements of `container` that overlap `range`.
+fn select<'tree>(
+    container: Node<'tree>,
+    code: &str,
+    range: &Range<usize>,
+    selected: &mut Vec<(Node<'tree>, usize)>,
+) {
+    let level = indentation_level(container);
+    let mut cursor = container.walk();
+    let statements = container
+        .named_children(&mut cursor)
+        .filter(|c| container.kind() != "methods" || c.kind() == "function_definition");
+    // The cvx commands indent the statements following them, see format_block.
+    let mut cvx_level = 0;
+    for statement in statements {
+        let command = command_name(statement, code);
+        if command == Some("cvx_end") {
+            cvx_level = 0;
+        }
+        if overlaps(statement, range) {
+            let inner = containers(statement)
+                .into_iter()
+                .find(|c| contains(statements_span(*c, code), range));
+            match inner {
+                Some(inner) => select(inner, code, range, selected),
+                None => selected.push((statement, level + cvx_level)),
+            }
+        }
+        if let Some("cvx_begin" | "subject") = command {
+            cvx_level += 1;
+        }
+    }
+}
+
+/// The nodes nested in a compound statement whose statements can be
+/// formatted on their own.
+fn containers(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    let mut containers = vec![];
+    for child in node.named_children(&mut cursor) {
+        if child.kind() == "block" || child.kind() == "methods" {
+            containers.push(child);
+        } else if CLAUSES.contains(&child.kind()) {
+            containers.extend(containers_of_clause(child));
+        }
+    }
+    containers
+}
+
+fn containers_of_clause(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    let blocks = node
+        .named_children(&mut cursor)
+        .filter(|c| c.kind() == "block")
+        .collect();
+    blocks
+}
+
+/// The number of levels statements directly inside `container` are indented.
+fn indentation_level(container: Node) -> usize {
+    let mut level = 0;
+    let mut node = Some(container);
+    while let Some(n) = node {
+        if n.parent().is_some() && INDENTING.contains(&n.kind()) {
+            level += 1;
+        }
+        node = n.parent();
+    }
+    level
+}
+
+/// The byte range covered by the lines of the statements of a container.
+fn statements_span(container: Node, code: &str) -> Range<usize> {
+    let mut cursor = container.walk();
+    let mut statements = container
+        .named_children(&mut cursor)
+        .filter(|c| container.kind() != "methods" || c.kind() == "function_definition");
+    let Some(first) = statements.next() else {
+        return 0..0;
+    };
+    let last = statements.last().unwrap_or(first);
+    let start = first.start_byte() - first.start_position().column;
+    let end = code[last.end_byte()..]
+        .find('\n')
+        .map_or(code.len(), |i| last.end_byte() + i);
+    start..end
+}
+
+fn command_name<'a>(node: Node, code: &'a str) -> Option<&'a str> {
+    if node.kind() != "command" {
+        return None;
+    }
+    node.named_child(0)?.utf8_text(code.as_bytes()).ok()
+}
+
+fn overlaps(node: Node, range: &Range<usize>) -> bool {
+    if range.is_empty() {
+        node.start_byte() <= range.start && range.start <= node.end_byte()
+    } else {
+        node.start_byte() < range.end && range.start < node.end_byte()
+    }
+}
+
+fn contains(outer: Range<usize>, inner: &Range<usize>) -> bool {
+    !outer.is_empty() && outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// The byte offset at which the 0-based `line` starts.
+fn line_start(code: &str, line: usize) -> usize {
+    if line == 0 {
+        return 0;
+    }
+    code.match_indices('\n')
+        .nth(line - 1)
+        .map_or(code.len(), |(i, _)| i + 1)
+}
@@ -0,0 +1,103 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::beautifier::{builtin_formatters, FormatContext};
+use super::error::FormatError;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+pub use tree_sitter::Node;
+
+/// Prints one kind of syntax node.
+///
+/// Implement this to change how a construct is printed, and register it in a
+/// [`FormatterRegistry`] under the tree-sitter kind of the nodes it handles:
+///
+/// ```
+/// use matlab_beautifier::{
+///     FormatContext, FormatError, FormatOptions, Formatter, FormatterRegistry, Node,
+///     NodeFormatter,
+/// };
+///
+/// struct Shout;
+///
+/// impl NodeFormatter for Shout {
+///     fn format(&self, context: &mut FormatContext, node: Node) -> Result<(), FormatError> {
+///         let text = context.text(node)?.to_uppercase();
+///         context.print(&text);
+///         Ok(())
+///     }
+/// }
+///
+/// let registry = FormatterRegistry::new().with_formatter("identifier", Shout);
+/// let formatter = Formatter::new(FormatOptions::default()).with_registry(registry);
+/// assert_eq!(formatter.format_str("x=y;\n")?, "X = Y;\n");
+/// # Ok::<(), FormatError>(())
+/// ```
+pub trait NodeFormatter: Send + Sync {
+    fn format(&self, context: &mut FormatContext, node: Node) -> Result<(), FormatError>;
+}
+
+/// The formatters used for each kind of syntax node.
+///
+/// A new registry holds the built-in formatters. Nodes of a kind without a
+/// formatter are printed as they are in the source.
+#[derive(Clone)]
+pub struct FormatterRegistry {
+    formatters: HashMap<&'static str, Arc<dyn NodeFormatter>>,
+}
+
+static BUILTIN: LazyLock<FormatterRegistry> = LazyLock::new(FormatterRegistry::new);
+
+impl FormatterRegistry {
+    /// Creates a registry holding the built-in formatters.
+    pub fn new() -> Self {
+        Self {
+            formatters: builtin_formatters().collect(),
+        }
+    }
+
+    /// The registry holding only the built-in formatters, shared by everyone
+    /// not registering their own.
+    pub(crate) fn builtin() -> &'static Self {
+        &BUILTIN
+    }
+
+    /// Formats nodes of kind `kind` with `formatter` instead.
+    pub fn with_formatter(
+        mut self,
+        kind: &'static str,
+        formatter: impl NodeFormatter + 'static,
+    ) -> Self {
+        self.formatters.insert(kind, Arc::new(formatter));
+        self
+    }
+
+    /// Prints nodes of kind `kind` as they are in the source.
+    pub fn without_formatter(mut self, kind: &str) -> Self {
+        self.formatters.remove(kind);
+        self
+    }
+
+    pub fn get(&self, kind: &str) -> Option<&dyn NodeFormatter> {
+        self.formatters.get(kind).map(Arc::as_ref)
+    }
+}
+
+impl Default for FormatterRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for FormatterRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut kinds: Vec<&str> = self.formatters.keys().copied().collect();
+        kinds.sort_unstable();
+        f.debug_struct("FormatterRegistry")
+            .field("kinds", &kinds)
+            .finish()
+    }
+}
@@ -0,0 +1,162 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::ops::Range;
+
+/// The output a node of the input was formatted into, as byte ranges.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Mapping {
+    pub input: Range<usize>,
+    pub output: Range<usize>,
+}
+
+/// Maps positions in the input to positions in the formatted output.
+///
+/// Recorded while formatting when [`FormatOptions::with_source_map`] is set.
+///
+/// [`FormatOptions::with_source_map`]: super::FormatOptions::with_source_map
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "Recorded")]
+pub struct SourceMap {
+    root: Mapping,
+    mappings: Vec<Mapping>,
+    /// The innermost mapping around each mapping, if any.
+    #[serde(skip_serializing)]
+    parents: Vec<Option<usize>>,
+    /// The mappings ordered by their end, then by their end in the output.
+    #[serde(skip_serializing)]
+    by_end: Vec<usize>,
+}
+
+/// The serialized form of a [`SourceMap`], without the derived indices.
+#[derive(Deserialize)]
+struct Recorded {
+    root: Mapping,
+    mappings: Vec<Mapping>,
+}
+
+impl From<Recorded> for SourceMap {
+    fn from(recorded: Recorded) -> Self {
+        let Recorded { root, mut mappings } = recorded;
+        // Nodes are recorded once formatted, so children come before their
+        // parents. The stable sort keeps it that way for equal spans.
+        mappings.sort_by_key(|m| m.input.start);
+
+        // Walk the nodes outermost first, keeping the chain of the ones
+        // around the current node on a stack.
+        let mut order: Vec<usize> = (0..mappings.len()).collect();
+        order.sort_by_key(|&i| {
+            (
+                mappings[i].input.start,
+                Reverse(mappings[i].input.end),
+                Reverse(i),
+            )
+        });
+        let mut parents = vec![None; mappings.len()];
+        let mut stack: Vec<usize> = Vec::new();
+        for i in order {
+            while stack
+                .last()
+                .is_some_and(|&top| !contains(&mappings[top].input, &mappings[i].input))
+            {
+                stack.pop();
+            }
+            parents[i] = stack.last().copied();
+            stack.push(i);
+        }
+
+        let mut by_end: Vec<usize> = (0..mappings.len()).collect();
+        by_end.sort_by_key(|&i| (mappings[i].input.end, mappings[i].output.end, Reverse(i)));
+
+        Self {
+            root,
+            mappings,
+            parents,
+            by_end,
+        }
+    }
+}
+
+impl SourceMap {
+    pub(crate) fn new(input_len: usize, output_len: usize, mappings: Vec<Mapping>) -> Self {
+        Recorded {
+            root: Mapping {
+                input: 0..input_len,
+                output: 0..output_len,
+            },
+            mappings,
+        }
+        .into()
+    }
+
+    /// All recorded node mappings, ordered by their start in the input.
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// Maps a byte offset of the input to the matching byte offset of the
+    /// output, e.g. to keep a cursor or a breakpoint in place.
+    ///
+    /// Offsets inside a node keep their distance to the closest formatted
+    /// node before them, so a cursor in an identifier stays at the same
+    /// character, while one in removed whitespace snaps to the next token.
+    pub fn map_position(&self, offset: usize) -> usize {
+        let offset = offset.min(self.root.input.end);
+        let container = self.container(offset);
+
+        // Nodes are nested, so whatever ends last before the offset is
+        // either inside the container or ends where it starts.
+        let ended = self
+            .by_end
+            .partition_point(|&i| self.mappings[i].input.end <= offset);
+        let before = self.by_end[..ended].last().and_then(|&last| {
+            let end = self.mappings[last].input.end;
+            self.by_end[..ended]
+                .iter()
+                .rev()
+                .map(|&i| &self.mappings[i])
+                .take_while(|m| m.input.end == end)
+                .find(|m| m.input.start >= container.input.start)
+        });
+        let started = self.mappings.partition_point(|m| m.input.start <= offset);
+        let after = self
+            .mappings
+            .get(started)
+            .filter(|m| contains(&container.input, &m.input));
+
+        let (input, output) = match before {
+            Some(before) => (before.input.end, before.output.end),
+            None => (container.input.start, container.output.start),
+        };
+        let limit = after.map_or(container.output.end, |a| a.output.start);
+        (output + (offset - input)).min(limit.max(output))
+    }
+
+    /// The smallest mapping around `offset`, or the root.
+    fn container(&self, offset: usize) -> &Mapping {
+        let started = self.mappings.partition_point(|m| m.input.start <= offset);
+        let Some(last) = started.checked_sub(1) else {
+            return &self.root;
+        };
+        // Every mapping around the offset is around the innermost one of
+        // those starting last, which comes first among them.
+        let start = self.mappings[last].input.start;
+        let mut index = Some(self.mappings.partition_point(|m| m.input.start < start));
+        while let Some(i) = index {
+            if self.mappings[i].input.contains(&offset) {
+                return &self.mappings[i];
+            }
+            index = self.parents[i];
+        }
+        &self.root
+    }
+}
+
+fn contains(outer: &Range<usize>, inner: &Range<usize>) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
@@ -0,0 +1,159 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::error::{FormatError, Result, Span};
+use super::formatter::parse;
+use tree_sitter::Node;
+
+/// Tokens the formatter may add, drop or replace without changing what the
+/// code means.
+const NORMALIZED_TOKENS: [&str; 4] = [",", ";", "end", "endfunction"];
+
+/// Checks that `formatted` means the same as `original`.
+///
+/// Both are parsed and their syntax trees compared node by node. Whitespace,
+/// line continuations and the punctuation the formatter normalizes are
+/// ignored. Comments may move and change their whitespace, but have to come in
+/// the same order. Fails with [`FormatError::NotEquivalent`] describing the
+/// first node or comment that differs.
+///
+/// ```
+/// use matlab_beautifier::verify_equivalent;
+///
+/// assert!(verify_equivalent("x=[1,2]", "x = [1 2];\n").is_ok());
+/// assert!(verify_equivalent("x=[1,2]", "x = [1 3];\n").is_err());
+/// ```
+pub fn verify_equivalent(original: &str, formatted: &str) -> Result<()> {
+    let original_tree = parse(original)?;
+    let formatted_tree = parse(formatted)?;
+    compare(
+        original_tree.root_node(),
+        original,
+        formatted_tree.root_node(),
+        formatted,
+    )
+}
+
+/// Compares the trees of `code` and of its formatted version `formatted`.
+pub(crate) fn compare(input: Node, code: &str, output: Node, formatted: &str) -> Result<()> {
+    let (input_nodes, input_comments) = significant_nodes(input, code.as_bytes());
+    let (output_nodes, output_comments) = significant_nodes(output, formatted.as_bytes());
+    for i in 0..input_nodes.len().max(output_nodes.len()) {
+        let (a, b) = (input_nodes.get(i), output_nodes.get(i));
+        let same = match (a, b) {
+            (Some(&(depth_a, a)), Some(&(depth_b, b))) => {
+                depth_a == depth_b
+                    && a.kind() == b.kind()
+                    && leaf_text(a, code.as_bytes()) == leaf_text(b, formatted.as_bytes())
+            }
+            _ => false,
+        };
+        if !same {
+            let input_nodes: Vec<Node> = input_nodes.iter().map(|&(_, node)| node).collect();
+            let output_nodes: Vec<Node> = output_nodes.iter().map(|&(_, node)| node).collect();
+            return Err(not_equivalent(
+                &input_nodes,
+                input,
+                &output_nodes,
+                output,
+                i,
+            ));
+        }
+    }
+    for i in 0..input_comments.len().max(output_comments.len()) {
+        let (a, b) = (input_comments.get(i), output_comments.get(i));
+        if a.map(|(_, text)| text) != b.map(|(_, text)| text) {
+            let input_nodes: Vec<Node> = input_comments.iter().map(|&(node, _)| node).collect();
+            let output_nodes: Vec<Node> = output_comments.iter().map(|&(node, _)| node).collect();
+            return Err(not_equivalent(
+                &input_nodes,
+                input,
+                &output_nodes,
+                output,
+                i,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A comment or line continuation, with its text without whitespace.
+type Comment<'t> = (Node<'t>, Vec<u8>);
+
+/// Describes that the `i`th of `input_nodes` and `output_nodes` differ.
+fn not_equivalent(
+    input_nodes: &[Node],
+    input: Node,
+    output_nodes: &[Node],
+    output: Node,
+    i: usize,
+) -> FormatError {
+    // When one side ran out of nodes, blame the last one it had.
+    let node = input_nodes.get(i).or(input_nodes.last()).unwrap_or(&input);
+    let output_node = output_nodes
+        .get(i)
+        .or(output_nodes.last())
+        .unwrap_or(&output);
+    FormatError::NotEquivalent {
+        span: Span::of(node),
+        kind: node.kind(),
+        output: Span::of(output_node),
+    }
+}
+
+/// The nodes that have to be the same in the input and the output, in
+/// pre-order, with their depth in the tree, and the comments with their text.
+///
+/// The formatter changes the whitespace in comments, so it is left out of
+/// their text. Line continuations without a comment are left out entirely, as
+/// wrapping adds and drops them.
+fn significant_nodes<'t>(
+    root: Node<'t>,
+    code: &[u8],
+) -> (Vec<(usize, Node<'t>)>, Vec<Comment<'t>>) {
+    let mut nodes = vec![];
+    let mut comments = vec![];
+    let mut stack = vec![(0, root)];
+    while let Some((depth, node)) = stack.pop() {
+        if node.kind() == "comment" || node.kind() == "line_continuation" {
+            let text = &code[node.byte_range()];
+            let text = match node.kind() {
+                "line_continuation" => text.strip_prefix(b"...").unwrap_or(text),
+                _ => text,
+            };
+            let text: Vec<u8> = text
+                .iter()
+                .copied()
+                .filter(|c| !c.is_ascii_whitespace())
+                .collect();
+            if !text.is_empty() {
+                comments.push((node, text));
+            }
+            continue;
+        }
+        if is_normalized(node, code) {
+            continue;
+        }
+        nodes.push((depth, node));
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev().map(|child| (depth + 1, child)));
+    }
+    (nodes, comments)
+}
+
+/// Whether the formatter may change `node` freely.
+fn is_normalized(node: Node, code: &[u8]) -> bool {
+    if node.is_named() {
+        return false;
+    }
+    NORMALIZED_TOKENS.contains(&node.kind())
+        || code[node.byte_range()].iter().all(u8::is_ascii_whitespace)
+}
+
+fn leaf_text<'c>(node: Node, code: &'c [u8]) -> Option<&'c [u8]> {
+    (node.child_count() == 0).then(|| &code[node.byte_range()])
+}
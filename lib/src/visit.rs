@@ -0,0 +1,325 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::ast::{
+    block_items, comments_after, directives, non_directive_comments, ArgumentsStatement, AstNode,
+    Block, CaseClause, CatchClause, ClassDefinition, ClassMember, Comment, ElseClause,
+    ElseIfClause, Enumeration, Events, ForStatement, FunctionDefinition, FunctionSignature,
+    IfStatement, Methods, OtherwiseClause, Properties, Property, SpmdStatement, SwitchStatement,
+    TryStatement, WhileStatement,
+};
+use tree_sitter::{Node, Tree};
+
+/// A statement with the comments that belong to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement<'t> {
+    node: Node<'t>,
+    leading_comments: Vec<Comment<'t>>,
+    trailing_comment: Option<Comment<'t>>,
+    directives: Vec<Comment<'t>>,
+}
+
+impl<'t> Statement<'t> {
+    pub fn node(&self) -> Node<'t> {
+        self.node
+    }
+
+    /// The comments right before the statement, up to the previous one.
+    pub fn leading_comments(&self) -> &[Comment<'t>] {
+        &self.leading_comments
+    }
+
+    /// The comment after the statement on its last line.
+    pub fn trailing_comment(&self) -> Option<Comment<'t>> {
+        self.trailing_comment
+    }
+
+    /// The linter directives on the header of a compound statement, like the
+    /// `%#ok` in `for i = 1:n %#ok`.
+    pub fn directives(&self) -> &[Comment<'t>] {
+        &self.directives
+    }
+}
+
+/// Hooks called while walking a syntax tree with [`walk`].
+///
+/// Every hook does nothing by default. A compound statement is entered after
+/// the statement holding it and left before it, with its blocks walked in
+/// between:
+///
+/// ```
+/// use matlab_beautifier::ast::{parse, IfStatement};
+/// use matlab_beautifier::{walk, Statement, Visitor};
+///
+/// #[derive(Default)]
+/// struct Counter {
+///     statements: usize,
+///     ifs: usize,
+///     comments: usize,
+/// }
+///
+/// impl<'t> Visitor<'t> for Counter {
+///     fn enter_statement(&mut self, statement: &Statement<'t>) {
+///         self.statements += 1;
+///         self.comments += statement.leading_comments().len();
+///     }
+///
+///     fn enter_if(&mut self, _statement: IfStatement<'t>) {
+///         self.ifs += 1;
+///     }
+/// }
+///
+/// let code = "% check\nif x\n    % set\n    y = 1;\nend\n";
+/// let tree = parse(code)?;
+/// let mut counter = Counter::default();
+/// walk(&tree, code, &mut counter);
+/// assert_eq!((counter.statements, counter.ifs, counter.comments), (2, 1, 2));
+/// # Ok::<(), matlab_beautifier::FormatError>(())
+/// ```
+#[allow(unused_variables)]
+pub trait Visitor<'t> {
+    fn enter_statement(&mut self, statement: &Statement<'t>) {}
+    fn leave_statement(&mut self, statement: &Statement<'t>) {}
+
+    /// A comment not belonging to any statement, e.g. at the end of a block
+    /// or in a clause without statements.
+    fn visit_dangling_comment(&mut self, comment: Comment<'t>) {}
+
+    fn enter_block(&mut self, block: Block<'t>) {}
+    fn leave_block(&mut self, block: Block<'t>) {}
+
+    fn enter_if(&mut self, statement: IfStatement<'t>) {}
+    fn leave_if(&mut self, statement: IfStatement<'t>) {}
+    fn enter_elseif(&mut self, clause: ElseIfClause<'t>) {}
+    fn leave_elseif(&mut self, clause: ElseIfClause<'t>) {}
+    fn enter_else(&mut self, clause: ElseClause<'t>) {}
+    fn leave_else(&mut self, clause: ElseClause<'t>) {}
+
+    fn enter_while(&mut self, statement: WhileStatement<'t>) {}
+    fn leave_while(&mut self, statement: WhileStatement<'t>) {}
+
+    fn enter_for(&mut self, statement: ForStatement<'t>) {}
+    fn leave_for(&mut self, statement: ForStatement<'t>) {}
+
+    fn enter_switch(&mut self, statement: SwitchStatement<'t>) {}
+    fn leave_switch(&mut self, statement: SwitchStatement<'t>) {}
+    fn enter_case(&mut self, clause: CaseClause<'t>) {}
+    fn leave_case(&mut self, clause: CaseClause<'t>) {}
+    fn enter_otherwise(&mut self, clause: OtherwiseClause<'t>) {}
+    fn leave_otherwise(&mut self, clause: OtherwiseClause<'t>) {}
+
+    fn enter_try(&mut self, statement: TryStatement<'t>) {}
+    fn leave_try(&mut self, statement: TryStatement<'t>) {}
+    fn enter_catch(&mut self, clause: CatchClause<'t>) {}
+    fn leave_catch(&mut self, clause: CatchClause<'t>) {}
+
+    fn enter_spmd(&mut self, statement: SpmdStatement<'t>) {}
+    fn leave_spmd(&mut self, statement: SpmdStatement<'t>) {}
+
+    fn enter_function(&mut self, function: FunctionDefinition<'t>) {}
+    fn leave_function(&mut self, function: FunctionDefinition<'t>) {}
+    fn enter_arguments(&mut self, statement: ArgumentsStatement<'t>) {}
+    fn leave_arguments(&mut self, statement: ArgumentsStatement<'t>) {}
+
+    fn enter_class(&mut self, class: ClassDefinition<'t>) {}
+    fn leave_class(&mut self, class: ClassDefinition<'t>) {}
+    fn enter_properties(&mut self, properties: Properties<'t>) {}
+    fn leave_properties(&mut self, properties: Properties<'t>) {}
+    fn enter_methods(&mut self, methods: Methods<'t>) {}
+    fn leave_methods(&mut self, methods: Methods<'t>) {}
+    fn visit_events(&mut self, events: Events<'t>) {}
+    fn visit_enumeration(&mut self, enumeration: Enumeration<'t>) {}
+
+    /// A property of a class or an argument in an `arguments` block.
+    fn visit_property(&mut self, property: Property<'t>) {}
+
+    /// A method declared without a body.
+    fn visit_signature(&mut self, signature: FunctionSignature<'t>) {}
+}
+
+/// Walks the statements of `tree`, parsed from `code`, calling the hooks of
+/// `visitor` in the order they are written in.
+pub fn walk<'t>(tree: &'t Tree, code: &'t str, visitor: &mut impl Visitor<'t>) {
+    let mut walker = Walker {
+        code: code.as_bytes(),
+        visitor,
+    };
+    walker.statements(tree.root_node());
+}
+
+struct Walker<'t, 'v, V> {
+    code: &'t [u8],
+    visitor: &'v mut V,
+}
+
+impl<'t, V: Visitor<'t>> Walker<'t, '_, V> {
+    /// Walks the statements of a block or the root, attaching the comments
+    /// in between to them.
+    fn statements(&mut self, node: Node<'t>) {
+        let mut statements: Vec<Statement> = vec![];
+        let mut comments: Vec<Comment> = vec![];
+        for item in block_items(node, self.code) {
+            let Some(comment) = Comment::cast(item) else {
+                statements.push(Statement {
+                    node: item,
+                    leading_comments: std::mem::take(&mut comments),
+                    trailing_comment: None,
+                    directives: directives(item, self.code).collect(),
+                });
+                continue;
+            };
+            match statements.last_mut() {
+                Some(previous)
+                    if comments.is_empty()
+                        && previous.trailing_comment.is_none()
+                        && previous.node.end_position().row == item.start_position().row =>
+                {
+                    previous.trailing_comment = Some(comment);
+                }
+                _ => comments.push(comment),
+            }
+        }
+        for statement in &statements {
+            self.statement(statement);
+        }
+        for comment in comments {
+            self.visitor.visit_dangling_comment(comment);
+        }
+    }
+
+    /// Walks `block`, or else the comments of `owner`, the clause it would
+    /// belong to. Comments of an empty `elseif` or `else` come `after` it.
+    fn body(&mut self, block: Option<Block<'t>>, owner: Node<'t>, after: bool) {
+        if let Some(block) = block {
+            self.visitor.enter_block(block);
+            self.statements(block.syntax());
+            self.visitor.leave_block(block);
+        } else if after {
+            let code = self.code;
+            for comment in comments_after(owner).take_while(|c| !c.is_directive(code)) {
+                self.visitor.visit_dangling_comment(comment);
+            }
+        } else {
+            self.dangling_comments(owner);
+        }
+    }
+
+    fn dangling_comments(&mut self, node: Node<'t>) {
+        for comment in non_directive_comments(node, self.code) {
+            self.visitor.visit_dangling_comment(comment);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement<'t>) {
+        self.visitor.enter_statement(statement);
+        let node = statement.node;
+        if let Some(statement) = IfStatement::cast(node) {
+            self.visitor.enter_if(statement);
+            self.body(statement.block(), node, false);
+            for clause in statement.elseif_clauses() {
+                self.visitor.enter_elseif(clause);
+                self.body(clause.block(), clause.syntax(), true);
+                self.visitor.leave_elseif(clause);
+            }
+            if let Some(clause) = statement.else_clause() {
+                self.visitor.enter_else(clause);
+                self.body(clause.block(), clause.syntax(), true);
+                self.visitor.leave_else(clause);
+            }
+            self.visitor.leave_if(statement);
+        } else if let Some(statement) = WhileStatement::cast(node) {
+            self.visitor.enter_while(statement);
+            self.body(statement.block(), node, false);
+            self.visitor.leave_while(statement);
+        } else if let Some(statement) = ForStatement::cast(node) {
+            self.visitor.enter_for(statement);
+            self.body(statement.block(), node, false);
+            self.visitor.leave_for(statement);
+        } else if let Some(statement) = SwitchStatement::cast(node) {
+            self.visitor.enter_switch(statement);
+            for clause in statement.cases() {
+                self.visitor.enter_case(clause);
+                self.body(clause.block(), clause.syntax(), false);
+                self.visitor.leave_case(clause);
+            }
+            if let Some(clause) = statement.otherwise() {
+                self.visitor.enter_otherwise(clause);
+                self.body(clause.block(), clause.syntax(), false);
+                self.visitor.leave_otherwise(clause);
+            }
+            self.visitor.leave_switch(statement);
+        } else if let Some(statement) = TryStatement::cast(node) {
+            self.visitor.enter_try(statement);
+            self.body(statement.block(), node, false);
+            if let Some(clause) = statement.catch_clause() {
+                self.visitor.enter_catch(clause);
+                self.body(clause.block(), clause.syntax(), false);
+                self.visitor.leave_catch(clause);
+            }
+            self.visitor.leave_try(statement);
+        } else if let Some(statement) = SpmdStatement::cast(node) {
+            self.visitor.enter_spmd(statement);
+            self.body(statement.block(), node, false);
+            self.visitor.leave_spmd(statement);
+        } else if let Some(function) = FunctionDefinition::cast(node) {
+            self.function(function);
+        } else if let Some(class) = ClassDefinition::cast(node) {
+            self.class(class);
+        }
+        self.visitor.leave_statement(statement);
+    }
+
+    fn function(&mut self, function: FunctionDefinition<'t>) {
+        self.visitor.enter_function(function);
+        for statement in function.arguments_statements() {
+            self.visitor.enter_arguments(statement);
+            for property in statement.properties() {
+                self.visitor.visit_property(property);
+            }
+            self.dangling_comments(statement.syntax());
+            self.visitor.leave_arguments(statement);
+        }
+        self.body(function.block(), function.syntax(), false);
+        self.visitor.leave_function(function);
+    }
+
+    fn class(&mut self, class: ClassDefinition<'t>) {
+        self.visitor.enter_class(class);
+        for member in class.members() {
+            match member {
+                ClassMember::Properties(properties) => {
+                    self.visitor.enter_properties(properties);
+                    for property in properties.properties() {
+                        self.visitor.visit_property(property);
+                    }
+                    self.dangling_comments(properties.syntax());
+                    self.visitor.leave_properties(properties);
+                }
+                ClassMember::Methods(methods) => {
+                    self.visitor.enter_methods(methods);
+                    for signature in methods.signatures() {
+                        self.visitor.visit_signature(signature);
+                    }
+                    for function in methods.definitions() {
+                        self.function(function);
+                    }
+                    self.dangling_comments(methods.syntax());
+                    self.visitor.leave_methods(methods);
+                }
+                ClassMember::Events(events) => {
+                    self.visitor.visit_events(events);
+                    self.dangling_comments(events.syntax());
+                }
+                ClassMember::Enumeration(enumeration) => {
+                    self.visitor.visit_enumeration(enumeration);
+                    self.dangling_comments(enumeration.syntax());
+                }
+            }
+        }
+        self.dangling_comments(class.syntax());
+        self.visitor.leave_class(class);
+    }
+}
@@ -0,0 +1,31 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// The columns between tab stops.
+const TAB_WIDTH: usize = 4;
+
+/// The column `text` ends at when printed from column `col` on, counting
+/// what is displayed rather than bytes: each grapheme cluster takes the width
+/// of its characters, two columns for East Asian wide ones, and tabs move to
+/// the next tab stop.
+pub(crate) fn advance(col: usize, text: &str) -> usize {
+    if text.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+        return col + text.len();
+    }
+    text.graphemes(true)
+        .fold(col, |col, grapheme| match grapheme {
+            "\t" => (col / TAB_WIDTH + 1) * TAB_WIDTH,
+            _ => col + grapheme.width(),
+        })
+}
+
+/// How many columns `text` takes when printed at the start of a line.
+pub(crate) fn display_width(text: &str) -> usize {
+    advance(0, text)
+}
@@ -0,0 +1,129 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for the typed view of the syntax tree.
+
+use matlab_beautifier::ast::{
+    parse, AstNode, ClassDefinition, FunctionDefinition, IfStatement, Matrix, Property,
+};
+
+fn text<'c>(node: matlab_beautifier::Node, code: &'c str) -> &'c str {
+    node.utf8_text(code.as_bytes()).unwrap()
+}
+
+#[test]
+fn test_if_statement() {
+    let code = "if a\n    x = 1;\nelseif b\n    x = 2;\nelseif c\nelse\n    x = 3;\nend\n";
+    let tree = parse(code).unwrap();
+    let node = tree.root_node().named_child(0).unwrap();
+    let statement = IfStatement::cast(node).unwrap();
+    assert_eq!(text(statement.condition().unwrap(), code), "a");
+    assert!(statement.block().is_some());
+    let conditions: Vec<&str> = statement
+        .elseif_clauses()
+        .map(|clause| text(clause.condition().unwrap(), code))
+        .collect();
+    assert_eq!(conditions, ["b", "c"]);
+    assert!(statement.else_clause().unwrap().block().is_some());
+}
+
+#[test]
+fn test_cast_checks_the_kind() {
+    let tree = parse("x = 1;\n").unwrap();
+    let node = tree.root_node().named_child(0).unwrap();
+    assert!(IfStatement::cast(node).is_none());
+    assert!(FunctionDefinition::cast(node).is_none());
+}
+
+#[test]
+fn test_function_definition() {
+    let code = "function [a, b] = f(x, y)\n    arguments\n        x (1,1) double\n        y\n    end\n    a = x;\nend\n";
+    let tree = parse(code).unwrap();
+    let node = tree.root_node().named_child(0).unwrap();
+    let function = FunctionDefinition::cast(node).unwrap();
+    assert_eq!(text(function.name().unwrap(), code), "f");
+    assert_eq!(
+        text(function.output().unwrap().value().unwrap(), code),
+        "[a, b]"
+    );
+    let arguments: Vec<&str> = function
+        .arguments()
+        .unwrap()
+        .arguments()
+        .map(|arg| text(arg, code))
+        .collect();
+    assert_eq!(arguments, ["x", "y"]);
+    let statement = function.arguments_statements().next().unwrap();
+    let properties: Vec<Property> = statement.properties().collect();
+    assert_eq!(properties.len(), 2);
+    assert_eq!(text(properties[0].class().unwrap(), code), "double");
+    assert!(properties[0].dimensions().is_some());
+    assert!(properties[1].class().is_none());
+}
+
+#[test]
+fn test_class_definition() {
+    let code = "classdef (Sealed) Foo < handle\n    properties (Access = private)\n        a = 1\n        b\n    end\n    methods\n        function obj = Foo()\n        end\n        r = get.a(obj)\n    end\n    events\n        Changed\n    end\nend\n";
+    let tree = parse(code).unwrap();
+    let node = tree.root_node().named_child(0).unwrap();
+    let class = ClassDefinition::cast(node).unwrap();
+    assert_eq!(text(class.name().unwrap(), code), "Foo");
+    let superclasses: Vec<&str> = class
+        .superclasses()
+        .unwrap()
+        .names()
+        .map(|name| text(name, code))
+        .collect();
+    assert_eq!(superclasses, ["handle"]);
+    let attribute = class.attributes().unwrap().attributes().next().unwrap();
+    assert_eq!(text(attribute.name().unwrap(), code), "Sealed");
+    assert!(attribute.value().is_none());
+
+    let properties = class.properties().next().unwrap();
+    let attribute = properties
+        .attributes()
+        .unwrap()
+        .attributes()
+        .next()
+        .unwrap();
+    assert_eq!(text(attribute.value().unwrap(), code), "private");
+    let defaults: Vec<Option<&str>> = properties
+        .properties()
+        .map(|property| property.default_value().map(|value| text(value, code)))
+        .collect();
+    assert_eq!(defaults, [Some("1"), None]);
+
+    let methods = class.methods().next().unwrap();
+    assert_eq!(methods.definitions().count(), 1);
+    let signature = methods.signatures().next().unwrap();
+    assert_eq!(
+        text(signature.accessor_prefix(code.as_bytes()).unwrap(), code),
+        "get."
+    );
+    let events: Vec<&str> = class
+        .events()
+        .next()
+        .unwrap()
+        .identifiers()
+        .map(|event| text(event, code))
+        .collect();
+    assert_eq!(events, ["Changed"]);
+}
+
+#[test]
+fn test_matrix_rows() {
+    let code = "x = {1, 2; 3, 4};\n";
+    let tree = parse(code).unwrap();
+    let assignment = tree.root_node().named_child(0).unwrap();
+    let node = assignment.child_by_field_name("right").unwrap();
+    let matrix = Matrix::cast(node).unwrap();
+    assert!(matrix.is_cell());
+    let rows: Vec<Vec<&str>> = matrix
+        .rows()
+        .map(|row| row.elements().map(|element| text(element, code)).collect())
+        .collect();
+    assert_eq!(rows, [["1", "2"], ["3", "4"]]);
+}
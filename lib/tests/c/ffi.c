@@ -0,0 +1,76 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+/* Exercises the C interface, see tests/ffi.rs. Exits with 1 on failure. */
+
+#include <matlab_beautifier.h>
+#include <stdio.h>
+#include <string.h>
+
+static int failures = 0;
+
+#define CHECK(condition)                                                     \
+    do {                                                                     \
+        if (!(condition)) {                                                  \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #condition);                                             \
+            failures++;                                                      \
+        }                                                                    \
+    } while (0)
+
+static void test_format(void) {
+    const char *code = "x=1+2\n";
+    mb_result result;
+    mb_status status = mb_format(code, strlen(code), NULL, &result);
+    CHECK(status == MB_STATUS_OK);
+    CHECK(result.text != NULL && strcmp(result.text, "x = 1+2;\n") == 0);
+    CHECK(result.len == strlen("x = 1+2;\n"));
+    CHECK(result.error == NULL);
+    CHECK(mb_error_message(&result) == NULL);
+    mb_result_free(&result);
+    CHECK(result.text == NULL);
+}
+
+static void test_options(void) {
+    const char *code = "x=1+2\n";
+    mb_options options = mb_options_default();
+    options.sparse_add = true;
+    mb_result result;
+    CHECK(mb_format(code, strlen(code), &options, &result) == MB_STATUS_OK);
+    CHECK(result.text != NULL && strcmp(result.text, "x = 1 + 2;\n") == 0);
+    mb_result_free(&result);
+}
+
+static void test_syntax_error(void) {
+    const char *code = "x = (1 +\n";
+    mb_result result;
+    CHECK(mb_format(code, strlen(code), NULL, &result) == MB_STATUS_SYNTAX_ERROR);
+    CHECK(result.text == NULL);
+    CHECK(mb_error_message(&result) != NULL);
+    CHECK(mb_error_line(&result) >= 1);
+    CHECK(mb_error_column(&result) >= 1);
+    mb_result_free(&result);
+}
+
+static void test_invalid_input(void) {
+    const char code[] = {'x', '=', (char)0xff, '\n'};
+    mb_result result;
+    CHECK(mb_format(code, sizeof(code), NULL, &result) == MB_STATUS_INVALID_UTF8);
+    CHECK(mb_error_line(&result) == 0);
+    mb_result_free(&result);
+    CHECK(mb_format(NULL, 3, NULL, &result) == MB_STATUS_INVALID_ARGUMENT);
+    mb_result_free(&result);
+    CHECK(mb_format("x", 1, NULL, NULL) == MB_STATUS_INVALID_ARGUMENT);
+    mb_result_free(NULL);
+}
+
+int main(void) {
+    test_format();
+    test_options();
+    test_syntax_error();
+    test_invalid_input();
+    return failures == 0 ? 0 : 1;
+}
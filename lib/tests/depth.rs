@@ -0,0 +1,82 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for formatting deeply nested and very long expressions.
+
+use matlab_beautifier::{beautify, FormatError, FormatOptions, Formatter};
+
+const TERMS: usize = 100_000;
+
+fn chain(operator: &str) -> String {
+    let terms: Vec<String> = (0..TERMS).map(|i| format!("x{}", i)).collect();
+    terms.join(operator)
+}
+
+#[test]
+fn test_long_operator_chain_is_formatted() {
+    let code = format!("y = {};\n", chain(" + "));
+    let options = FormatOptions::new().with_verify(true);
+    assert_eq!(
+        beautify(&code, &options).unwrap(),
+        format!("y = {};\n", chain("+"))
+    );
+}
+
+#[test]
+fn test_long_boolean_chain_is_formatted() {
+    let code = format!("y = {};\n", chain("&&"));
+    let formatted = beautify(&code, &FormatOptions::new()).unwrap();
+    assert_eq!(formatted, format!("y = {};\n", chain(" && ")));
+}
+
+#[test]
+fn test_long_operator_chain_is_mapped() {
+    let code = format!("y = {};\n", chain("+"));
+    let formatter = Formatter::new(FormatOptions::new().with_source_map(true));
+    let formatted = formatter.format(&code).unwrap();
+    let map = formatted.source_map.unwrap();
+    let input = code.find("x99999").unwrap();
+    assert_eq!(map.map_position(input), input);
+}
+
+#[test]
+fn test_long_operator_chain_is_wrapped() {
+    let code = format!("y = {};\n", chain("+"));
+    let options = FormatOptions::new().with_max_width(Some(80));
+    let formatted = beautify(&code, &options).unwrap();
+    assert!(formatted.lines().all(|line| line.len() <= 80));
+}
+
+#[test]
+fn test_deep_nesting_fails_cleanly() {
+    let code = format!("y = {}1{};\n", "(".repeat(TERMS), ")".repeat(TERMS));
+    let err = beautify(&code, &FormatOptions::new()).unwrap_err();
+    assert!(matches!(err, FormatError::TooDeep { limit: 256, .. }));
+}
+
+#[test]
+fn test_deep_unary_operators_fail_cleanly() {
+    let code = format!("y = {}x;\n", "-".repeat(TERMS));
+    let err = beautify(&code, &FormatOptions::new()).unwrap_err();
+    assert!(matches!(err, FormatError::TooDeep { .. }));
+}
+
+#[test]
+fn test_max_depth_is_configurable() {
+    let code = "y = ((1));\n";
+    let options = FormatOptions::new().with_max_depth(2);
+    let err = beautify(code, &options).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::TooDeep {
+            kind: "parenthesis",
+            limit: 2,
+            ..
+        }
+    ));
+    let options = FormatOptions::new().with_max_depth(8);
+    assert_eq!(beautify(code, &options).unwrap(), "y = ((1));\n");
+}
@@ -0,0 +1,149 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for laying out documents against a line width.
+
+use matlab_beautifier::Doc;
+
+fn call(args: &[&str]) -> Doc {
+    let mut docs = vec![];
+    for (i, arg) in args.iter().enumerate() {
+        if i != 0 {
+            docs.push(Doc::text(","));
+            docs.push(Doc::line(" ", " ..."));
+        }
+        docs.push(Doc::text(*arg));
+    }
+    Doc::group(vec![Doc::text("f("), Doc::align(docs), Doc::text(")")])
+}
+
+#[test]
+fn test_group_stays_flat_when_it_fits() {
+    assert_eq!(call(&["a", "b", "c"]).render(80), "f(a, b, c)");
+}
+
+#[test]
+fn test_group_breaks_all_lines_when_too_long() {
+    assert_eq!(
+        call(&["alpha", "beta", "gamma"]).render(12),
+        "f(alpha, ...\n  beta, ...\n  gamma)"
+    );
+}
+
+#[test]
+fn test_nested_group_breaks_independently() {
+    let doc = Doc::group(vec![
+        Doc::text("x = "),
+        Doc::indent(
+            4,
+            vec![call(&["a", "b"]), Doc::line(" ", ""), Doc::text("+ y")],
+        ),
+    ]);
+    assert_eq!(doc.render(12), "x = f(a, b)\n    + y");
+}
+
+#[test]
+fn test_fill_breaks_only_where_needed() {
+    let mut docs = vec![];
+    for i in 1..=8 {
+        if i != 1 {
+            docs.push(Doc::line(" ", " ..."));
+        }
+        docs.push(Doc::text(i.to_string()));
+    }
+    let doc = Doc::fill(vec![Doc::text("m = ["), Doc::align(docs), Doc::text("];")]);
+    assert_eq!(doc.render(16), "m = [1 2 3 4 ...\n     5 6 7 8];");
+}
+
+#[test]
+fn test_fill_keeps_next_fill_flat_if_possible() {
+    let product = |a: &str, b: &str| {
+        Doc::fill(vec![Doc::align(vec![
+            Doc::text(format!("{a}*")),
+            Doc::line("", " ..."),
+            Doc::text(b),
+        ])])
+    };
+    let doc = Doc::group(vec![
+        Doc::text("y = "),
+        Doc::fill(vec![Doc::align(vec![
+            product("aaaa", "bbbb"),
+            Doc::text("+"),
+            Doc::line("", " ..."),
+            product("cccc", "dddd"),
+        ])]),
+        Doc::text(";"),
+    ]);
+    assert_eq!(doc.render(20), "y = aaaa*bbbb+ ...\n    cccc*dddd;");
+}
+
+#[test]
+fn test_hard_line_breaks_enclosing_group() {
+    let doc = Doc::group(vec![
+        Doc::text("a"),
+        Doc::line(" ", ""),
+        Doc::text("b"),
+        Doc::HardLine,
+        Doc::text("c"),
+    ]);
+    assert_eq!(doc.render(80), "a\nb\nc");
+}
+
+#[test]
+fn test_hard_line_ends_what_has_to_fit_in_fill() {
+    let doc = |first: &str| {
+        Doc::fill(vec![
+            Doc::text(first),
+            Doc::line(" ", ""),
+            Doc::text("b"),
+            Doc::HardLine,
+            Doc::text("a very long line after the break"),
+        ])
+    };
+    assert_eq!(doc("a").render(10), "a b\na very long line after the break");
+    assert_eq!(
+        doc("aaaaaaaaa").render(10),
+        "aaaaaaaaa\nb\na very long line after the break"
+    );
+}
+
+#[test]
+fn test_hard_line_continues_at_alignment() {
+    let doc = Doc::group(vec![
+        Doc::text("x = "),
+        Doc::align(vec![
+            Doc::text("[1;"),
+            Doc::HardLine,
+            Doc::HardLine,
+            Doc::text("2]"),
+        ]),
+    ]);
+    assert_eq!(doc.render(80), "x = [1;\n\n    2]");
+}
+
+#[test]
+fn test_marks_report_output_offsets() {
+    let doc = Doc::group(vec![
+        Doc::text("abc"),
+        Doc::Mark(1),
+        Doc::line(" ", ""),
+        Doc::Mark(0),
+    ]);
+    assert_eq!(doc.render_marked(80), ("abc ".to_string(), vec![4, 3]));
+}
+
+#[test]
+fn test_wide_characters_take_two_columns() {
+    let doc = call(&["'長さ'", "'速度'"]);
+    assert_eq!(doc.render(17), "f('長さ', '速度')");
+    assert_eq!(doc.render(16), "f('長さ', ...\n  '速度')");
+}
+
+#[test]
+fn test_combining_characters_take_no_column() {
+    let doc = call(&["'A\u{30a}'", "'b'"]);
+    assert_eq!(doc.render(11), "f('A\u{30a}', 'b')");
+}
@@ -0,0 +1,90 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for incrementally formatting an edited document.
+
+use matlab_beautifier::{beautify, Document, FormatError, FormatOptions, InputEdit, Point};
+
+const CODE: &str = "x=1;\nif a\n  y=2;\nend\nfunction f()\nz=3;\nend\n";
+
+fn assert_matches_beautify(document: &mut Document) {
+    let expected = beautify(document.source(), document.options()).unwrap();
+    assert_eq!(document.format().unwrap(), expected);
+}
+
+#[test]
+fn test_document_formats_like_beautify() {
+    let mut document = Document::new(CODE, FormatOptions::default()).unwrap();
+    assert_matches_beautify(&mut document);
+    assert_matches_beautify(&mut document);
+}
+
+#[test]
+fn test_document_after_replacements() {
+    let mut document = Document::new(CODE, FormatOptions::default()).unwrap();
+    assert_matches_beautify(&mut document);
+    document.replace(2..3, "10+2").unwrap();
+    assert_matches_beautify(&mut document);
+    let z = document.source().find("z=3").unwrap();
+    document.replace(z..z + 3, "z = [1,2;3,4]").unwrap();
+    assert_matches_beautify(&mut document);
+    let len = document.source().len();
+    document.replace(len..len, "w=x*2;\n").unwrap();
+    assert_matches_beautify(&mut document);
+}
+
+#[test]
+fn test_document_after_input_edit() {
+    let mut document = Document::new("a=1;\nb=2;\n", FormatOptions::default()).unwrap();
+    assert_matches_beautify(&mut document);
+    let edit = InputEdit {
+        start_byte: 5,
+        old_end_byte: 5,
+        new_end_byte: 14,
+        start_position: Point::new(1, 0),
+        old_end_position: Point::new(1, 0),
+        new_end_position: Point::new(2, 0),
+    };
+    document.edit(&edit, "a=1;\nc=[1,2];\nb=2;\n").unwrap();
+    assert_eq!(document.format().unwrap(), "a = 1;\nc = [1 2];\nb = 2;\n");
+}
+
+#[test]
+fn test_document_after_token_change() {
+    // Renaming keeps the shape of the tree, so only the edit tells that the
+    // statement changed.
+    let mut document = Document::new("x=1;\ny=2;\n", FormatOptions::default()).unwrap();
+    assert_matches_beautify(&mut document);
+    document.replace(0..1, "z").unwrap();
+    assert_eq!(document.format().unwrap(), "z = 1;\ny = 2;\n");
+    document.replace(5..6, "w").unwrap();
+    assert_eq!(document.format().unwrap(), "z = 1;\nw = 2;\n");
+}
+
+#[test]
+fn test_tolerant_document_formats_like_beautify() {
+    let code = "function f\nx=1;\ny = = 2;\nend\nz=3;\n";
+    let options = FormatOptions::new().with_tolerant(true);
+    let mut document = Document::new(code, options).unwrap();
+    assert_matches_beautify(&mut document);
+    document
+        .replace(code.len() - 3..code.len() - 2, "4")
+        .unwrap();
+    assert_matches_beautify(&mut document);
+}
+
+#[test]
+fn test_document_rejects_invalid_ranges() {
+    let mut document = Document::new("x='µ';\n", FormatOptions::default()).unwrap();
+    let reversed = std::ops::Range { start: 2, end: 1 };
+    for range in [3..4, reversed, 7..9] {
+        assert!(matches!(
+            document.replace(range, "a"),
+            Err(FormatError::InvalidRange { .. })
+        ));
+    }
+    assert_eq!(document.source(), "x='µ';\n");
+}
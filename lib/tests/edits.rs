@@ -0,0 +1,84 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for formatting as a list of text edits.
+
+use matlab_beautifier::{
+    apply_edits, beautify, compute_edits, text_edits, FormatError, FormatOptions, TextEdit,
+    TextPosition, TextRange,
+};
+
+#[test]
+fn test_edits_reproduce_beautify() {
+    let code = "x=1;\nif a\ny=[1,2;3,4];\nend\nw  =  4\n";
+    let options = FormatOptions::default();
+    let edits = text_edits(code, &options).unwrap();
+    assert_eq!(
+        apply_edits(code, &edits).unwrap(),
+        beautify(code, &options).unwrap()
+    );
+}
+
+#[test]
+fn test_edits_are_minimal() {
+    let edits = compute_edits("x=1;\ny = 2;\n", "x = 1;\ny = 2;\n");
+    assert_eq!(edits.len(), 2);
+    assert!(edits.iter().all(|e| e.new_text == " "));
+    assert_eq!(edits[0].range.start.offset, 1);
+    assert_eq!(edits[1].range.start.offset, 2);
+    assert!(edits.iter().all(|e| e.range.start.line == 0));
+}
+
+#[test]
+fn test_edit_positions_use_utf16_columns() {
+    let edits = compute_edits("s='😀';x=1;\n", "s='😀';x = 1;\n");
+    assert_eq!(edits[0].range.start.offset, 10);
+    assert_eq!(edits[0].range.start.line, 0);
+    assert_eq!(edits[0].range.start.character, 8);
+}
+
+#[test]
+fn test_no_edits_for_formatted_code() {
+    assert!(compute_edits("x = 1;\n", "x = 1;\n").is_empty());
+}
+
+/// Replaces the bytes in `range` of the first line by `new_text`.
+fn edit(range: std::ops::Range<usize>, new_text: &str) -> TextEdit {
+    let position = |offset| TextPosition {
+        offset,
+        line: 0,
+        character: offset,
+    };
+    TextEdit {
+        range: TextRange {
+            start: position(range.start),
+            end: position(range.end),
+        },
+        new_text: new_text.to_string(),
+    }
+}
+
+#[test]
+fn test_invalid_edits_are_reported() {
+    let code = "s='😀';x=1;\n";
+    assert_eq!(
+        apply_edits(code, &[edit(0..1, "t"), edit(9..9, " ")]).unwrap(),
+        "t='😀'; x=1;\n"
+    );
+    for edits in [
+        vec![edit(9..9, " "), edit(0..1, "t")],
+        vec![edit(0..2, "t="), edit(1..3, "='")],
+        vec![edit(9..20, "")],
+        vec![edit(4..5, "")],
+    ] {
+        let err = apply_edits(code, &edits).unwrap_err();
+        assert!(
+            matches!(err, FormatError::InvalidRange { .. }),
+            "expected an invalid range, got {:?}",
+            err
+        );
+    }
+}
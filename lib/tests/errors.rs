@@ -0,0 +1,110 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for the errors and diagnostics reported on invalid input.
+
+use matlab_beautifier::{
+    beautify, Diagnostic, FormatError, FormatOptions, Formatter, Position, Span,
+};
+
+fn syntax_error(code: &str) -> FormatError {
+    let err = beautify(code, &FormatOptions::default()).unwrap_err();
+    assert!(
+        matches!(err, FormatError::Syntax { .. }),
+        "expected a syntax error, got {:?}",
+        err
+    );
+    err
+}
+
+#[test]
+fn test_syntax_error_is_reported_with_location() {
+    let err = syntax_error("x = 1;\ny = (2 + ;\n");
+    assert_eq!(err.span().unwrap().start.line, 2);
+}
+
+#[test]
+fn test_every_syntax_error_gets_a_diagnostic() {
+    let err = syntax_error("x = (1 + ;\ny = 2;\nz = [3 4;\n");
+    let diagnostics = err.diagnostics();
+    assert!(diagnostics.len() >= 2, "{:#?}", diagnostics);
+    assert!(diagnostics.windows(2).all(|d| d[0].span <= d[1].span));
+}
+
+#[test]
+fn test_wide_characters_are_underlined_by_width() {
+    let line = "x = '日本' + \t(";
+    let underline = |text: &str| {
+        let start = line.find(text).unwrap();
+        let diagnostic = Diagnostic {
+            file: None,
+            span: Span {
+                start: Position {
+                    line: 1,
+                    column: start + 1,
+                },
+                end: Position {
+                    line: 1,
+                    column: start + text.len() + 1,
+                },
+            },
+            kind: "ERROR".to_string(),
+            missing: false,
+            message: "unexpected input".to_string(),
+            hint: None,
+            line: line.to_string(),
+        };
+        diagnostic.render().lines().nth(4).unwrap().to_string()
+    };
+    assert_eq!(underline("日本"), "  |      ^^^^");
+    assert_eq!(underline("("), "  |              \t^");
+}
+
+#[test]
+fn test_missing_end_is_hinted() {
+    let err = syntax_error("function f(x)\n    if x > 1\n        y = 2;\nend\n");
+    let diagnostic = &err.diagnostics()[0];
+    let rendered = diagnostic.render();
+    assert!(rendered.contains("<input>:"), "{}", rendered);
+    assert!(rendered.contains('^'), "{}", rendered);
+    assert_eq!(
+        diagnostic.hint.as_deref(),
+        Some("unterminated block: missing `end`"),
+        "{}",
+        rendered
+    );
+}
+
+#[test]
+fn test_tolerant_mode_formats_around_errors() {
+    let code = "x=1;\ny = (2 + ;\nfunction f\nz  =  3;\nend\n";
+    let formatter = Formatter::new(FormatOptions::new().with_tolerant(true));
+    let formatted = formatter.format(code).unwrap();
+    assert!(formatted.text.starts_with("x = 1;\n"), "{}", formatted.text);
+    assert!(formatted.text.contains("y = (2 + "), "{}", formatted.text);
+    assert!(
+        formatted.text.contains("    z = 3;\n"),
+        "{}",
+        formatted.text
+    );
+    assert_eq!(formatted.untouched.len(), 1);
+    assert_eq!(formatted.untouched[0].start.line, 2);
+    assert!(!formatted.diagnostics.is_empty());
+}
+
+#[test]
+fn test_tolerant_mode_keeps_broken_statement_in_healthy_block() {
+    let code = "if a\nb=1;\nc = [1 2;\nend\n";
+    let formatter = Formatter::new(FormatOptions::new().with_tolerant(true));
+    let formatted = formatter.format(code).unwrap();
+    assert_eq!(formatted.text, "if a\n    b = 1;\n    c = [1 2;\nend\n");
+    assert_eq!(formatted.untouched.len(), 1);
+    assert_eq!(
+        formatted.untouched[0].start,
+        Position { line: 3, column: 1 }
+    );
+    assert_eq!(formatted.untouched[0].end.line, 3);
+}
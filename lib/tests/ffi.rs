@@ -0,0 +1,55 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for the C interface: the checked-in header, and the C program in
+//! `tests/c` compiled and run against the shared library.
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_header_is_up_to_date() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/matlab_beautifier.h"));
+    let checked_in = std::fs::read_to_string(manifest_dir.join("include/matlab_beautifier.h"));
+    assert!(
+        checked_in.unwrap() == generated,
+        "include/matlab_beautifier.h is outdated, build with \
+         MATLAB_BEAUTIFIER_UPDATE_HEADER=1 to update it"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_c_interface() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // The test runs from target/<profile>/deps, next to the shared library.
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    let program = deps.join("ffi_c_test");
+    let compiled = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(manifest_dir.join("tests/c/ffi.c"))
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(deps)
+        .arg("-lmatlab_beautifier")
+        .arg(format!("-Wl,-rpath,{}", deps.display()))
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(compiled.success(), "compiling tests/c/ffi.c failed");
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "tests/c/ffi.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,18 @@
+function test_unicode
+    sizes = {'Größe'   'µm';
+             'Länge'   'mm';
+             'Breite'  'cm'};
+    labels = {'長さ'   'm';
+              '速度'   'm/s';
+              'Temp.'  '°C'};
+    units = {'µm'   1e-6;
+             'Å'    1e-10;
+             'Å'    1e-10;
+             'mm²'  1e-6};
+    name = 'Größe'; % first line
+                    % second line
+    unit = '長さ'; % 単位
+                   % メートル
+    tabbed = 'a	b'; % tab
+                    % stop
+end
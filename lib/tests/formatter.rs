@@ -15,16 +15,7 @@
 //!   1. Create `tests/fixtures/<name>.m` with valid, already-formatted MATLAB.
 //!   2. Add `fixture_test!(test_<name>, "<name>.m");` below.
 
-use matlab_beautifier::{beautify, Arguments};
-
-fn make_args() -> Arguments {
-    Arguments {
-        files: vec![],
-        sparse_math: false,
-        sparse_add: false,
-        inplace: true,
-    }
-}
+use matlab_beautifier::{beautify, line_diff, FormatError, FormatOptions, Formatter};
 
 fn assert_idempotent(fixture_name: &str) {
     let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -34,27 +25,14 @@ fn assert_idempotent(fixture_name: &str) {
     let content = std::fs::read_to_string(&path)
         .unwrap_or_else(|e| panic!("Could not read fixture '{}': {}", fixture_name, e));
 
-    let mut args = make_args();
-    let result = beautify(&content, &mut args)
+    let result = beautify(&content, &FormatOptions::default())
         .unwrap_or_else(|e| panic!("beautify() failed for '{}': {}", fixture_name, e));
 
     if content != result {
-        // Build a simple line-diff to make failures easy to diagnose.
-        let expected_lines: Vec<&str> = content.lines().collect();
-        let got_lines: Vec<&str> = result.lines().collect();
-        let mut diff = String::new();
-        let max = expected_lines.len().max(got_lines.len());
-        for i in 0..max {
-            let exp = expected_lines.get(i).copied().unwrap_or("<missing>");
-            let got = got_lines.get(i).copied().unwrap_or("<missing>");
-            if exp != got {
-                diff.push_str(&format!("  line {:>3} expected: {:?}\n", i + 1, exp));
-                diff.push_str(&format!("  line {:>3}      got: {:?}\n", i + 1, got));
-            }
-        }
         panic!(
             "Formatter output for '{}' is not idempotent.\nDiffs:\n{}",
-            fixture_name, diff
+            fixture_name,
+            line_diff(&content, &result)
         );
     }
 }
@@ -102,3 +80,41 @@ fixture_test!(test_property_name, "property_name.m");
 fixture_test!(test_comment, "comment.m");
 fixture_test!(test_command, "command.m");
 fixture_test!(test_line_continuation, "line_continuation.m");
+fixture_test!(test_unicode, "unicode.m");
+
+// -- Formatter API ------------------------------------------------------------
+#[test]
+fn test_formatter_shared_between_threads() {
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let formatter = Formatter::new(FormatOptions::default());
+    std::thread::scope(|scope| {
+        for name in ["assignment.m", "matrix_cell.m", "class_definition.m"] {
+            let path = fixtures.join(name);
+            let formatter = &formatter;
+            scope.spawn(move || {
+                let expected = std::fs::read_to_string(&path).unwrap();
+                for _ in 0..3 {
+                    assert_eq!(formatter.format_str(&expected).unwrap(), expected);
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn test_ensure_idempotent_accepts_stable_output() {
+    let options = FormatOptions::new().with_ensure_idempotent(true);
+    let formatted = beautify("x=1;\nif x\ny=[1,2;3,4];\nend\n", &options).unwrap();
+    assert_eq!(formatted, "x = 1;\nif x\n    y = [1 2; 3 4];\nend\n");
+}
+
+#[test]
+fn test_not_idempotent_error_carries_diff() {
+    let err = FormatError::NotIdempotent {
+        diff: line_diff("a\nb\n", "a\n"),
+    };
+    assert_eq!(
+        err.to_string(),
+        "formatting is not idempotent:\n  line   2 expected: \"b\"\n  line   2      got: \"<missing>\"\n"
+    );
+}
@@ -0,0 +1,91 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for formatting fragments of files and single expressions.
+
+use matlab_beautifier::{format_expression, format_fragment, FormatError, FormatOptions};
+
+#[test]
+fn test_fragment_is_indented_by_base_level() {
+    let code = "for i=1:n\ndisp(i)\nend\nx=2;\n";
+    assert_eq!(
+        format_fragment(code, 2, &FormatOptions::new()).unwrap(),
+        "        for i = 1:n\n            disp(i);\n        end\n        x = 2;"
+    );
+}
+
+#[test]
+fn test_fragment_keeps_separator_of_last_statement() {
+    let options = FormatOptions::new();
+    assert_eq!(
+        format_fragment("x=1\ny=2", 0, &options).unwrap(),
+        "x = 1;\ny = 2"
+    );
+    assert_eq!(
+        format_fragment("x=1\ny=2;", 0, &options).unwrap(),
+        "x = 1;\ny = 2;"
+    );
+    assert_eq!(format_fragment("y=2,", 0, &options).unwrap(), "y = 2,");
+    assert_eq!(
+        format_fragment("y=2 % two", 0, &options).unwrap(),
+        "y = 2 % two"
+    );
+}
+
+#[test]
+fn test_fragment_can_be_terminated_like_a_file() {
+    let options = FormatOptions::new().with_terminate_fragments(true);
+    assert_eq!(format_fragment("y=2", 1, &options).unwrap(), "    y = 2;\n");
+}
+
+#[test]
+fn test_empty_fragment() {
+    assert_eq!(format_fragment("", 1, &FormatOptions::new()).unwrap(), "");
+}
+
+#[test]
+fn test_fragment_is_checked_when_asked() {
+    let options = FormatOptions::new()
+        .with_verify(true)
+        .with_ensure_idempotent(true);
+    let code = "if a,b=[1,2;3,4],end";
+    assert_eq!(
+        format_fragment(code, 1, &options).unwrap(),
+        "    if a\n        b = [1 2; 3 4];\n    end"
+    );
+}
+
+#[test]
+fn test_expression() {
+    let options = FormatOptions::new().with_sparse_add(true);
+    assert_eq!(format_expression("a+b*c", &options).unwrap(), "a + b*c");
+    assert_eq!(
+        format_expression("@(x)x.^2", &options).unwrap(),
+        "@(x) x.^2"
+    );
+}
+
+#[test]
+fn test_statements_are_not_expressions() {
+    let options = FormatOptions::new();
+    for code in [
+        "x = 1",
+        "a\nb",
+        "if a\nend",
+        "return",
+        "break",
+        "continue",
+        "",
+    ] {
+        let err = format_expression(code, &options).unwrap_err();
+        assert!(
+            matches!(err, FormatError::NotAnExpression { .. }),
+            "{:?}: expected NotAnExpression, got {:?}",
+            code,
+            err
+        );
+    }
+}
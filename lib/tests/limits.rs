@@ -0,0 +1,172 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for limiting the size of the input and the time spent on it.
+
+use matlab_beautifier::{
+    format_expression, format_fragment, format_range, CancellationToken, Document, FormatError,
+    FormatOptions, FormatRange, Formatter,
+};
+use std::thread;
+use std::time::Duration;
+
+/// Many statements, to parse and format for a while.
+fn long_code() -> String {
+    "x = [1, 2; 3, 4] * y';\n".repeat(50_000)
+}
+
+#[test]
+fn test_limits_are_off_by_default() {
+    let formatter = Formatter::default();
+    assert_eq!(formatter.format_str("x=1\n").unwrap(), "x = 1;\n");
+}
+
+#[test]
+fn test_input_size_is_limited() {
+    let options = FormatOptions::new().with_max_input_size(Some(8));
+    let formatter = Formatter::new(options);
+    assert_eq!(formatter.format_str("x = 1;\n").unwrap(), "x = 1;\n");
+    assert!(matches!(
+        formatter.format_str("x = 1 + 2;\n"),
+        Err(FormatError::InputTooLarge { limit: 8 })
+    ));
+}
+
+#[test]
+fn test_input_size_of_reader_is_limited() {
+    let options = FormatOptions::new().with_max_input_size(Some(6));
+    let formatter = Formatter::new(options);
+    assert_eq!(formatter.format_reader(&b"x = 1;"[..]).unwrap(), "x = 1;\n");
+    assert!(matches!(
+        formatter.format_reader(&b"x = 12;"[..]),
+        Err(FormatError::InputTooLarge { limit: 6 })
+    ));
+}
+
+#[test]
+fn test_parsing_times_out() {
+    let options = FormatOptions::new().with_parse_timeout(Some(Duration::ZERO));
+    let err = Formatter::new(options)
+        .format_str(&long_code())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::ParseTimeout {
+            limit: Duration::ZERO
+        }
+    ));
+}
+
+#[test]
+fn test_parser_is_reset_after_timeout() {
+    let options = FormatOptions::new().with_parse_timeout(Some(Duration::ZERO));
+    assert!(Formatter::new(options).format_str(&long_code()).is_err());
+    let formatter = Formatter::default();
+    assert_eq!(formatter.format_str("y=2\n").unwrap(), "y = 2;\n");
+}
+
+#[test]
+fn test_formatting_times_out() {
+    let options = FormatOptions::new().with_format_timeout(Some(Duration::ZERO));
+    let err = Formatter::new(options).format_str("x = 1;\n").unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::FormatTimeout {
+            limit: Duration::ZERO
+        }
+    ));
+}
+
+#[test]
+fn test_formatting_is_cancelled() {
+    let token = CancellationToken::new();
+    let formatter = Formatter::default().with_cancellation(token.clone());
+    assert!(formatter.format_str("x = 1;\n").is_ok());
+    token.cancel();
+    assert!(token.is_cancelled());
+    assert!(matches!(
+        formatter.format_str("x = 1;\n"),
+        Err(FormatError::Cancelled)
+    ));
+}
+
+#[test]
+fn test_formatting_is_cancelled_from_another_thread() {
+    let token = CancellationToken::new();
+    let formatter = Formatter::default().with_cancellation(token.clone());
+    let code = long_code().repeat(20);
+    let result = thread::scope(|scope| {
+        let formatting = scope.spawn(|| formatter.format_str(&code));
+        thread::sleep(Duration::from_millis(10));
+        token.cancel();
+        formatting.join().unwrap()
+    });
+    assert!(matches!(result, Err(FormatError::Cancelled)));
+}
+
+#[test]
+fn test_limits_apply_to_fragments() {
+    let options = FormatOptions::new().with_max_input_size(Some(8));
+    assert!(matches!(
+        format_fragment("x = 1 + 2;", 1, &options),
+        Err(FormatError::InputTooLarge { limit: 8 })
+    ));
+    let options = FormatOptions::new().with_format_timeout(Some(Duration::ZERO));
+    assert!(matches!(
+        format_fragment("x = 1;", 1, &options),
+        Err(FormatError::FormatTimeout { .. })
+    ));
+}
+
+#[test]
+fn test_limits_apply_to_expressions() {
+    let options = FormatOptions::new().with_max_input_size(Some(4));
+    assert!(matches!(
+        format_expression("a + b", &options),
+        Err(FormatError::InputTooLarge { limit: 4 })
+    ));
+    let token = CancellationToken::new();
+    token.cancel();
+    let formatter = Formatter::default().with_cancellation(token);
+    assert!(matches!(
+        formatter.format_expression("a + b"),
+        Err(FormatError::Cancelled)
+    ));
+}
+
+#[test]
+fn test_limits_apply_to_ranges() {
+    let options = FormatOptions::new().with_parse_timeout(Some(Duration::ZERO));
+    let err = format_range(&long_code(), &[FormatRange::Lines(1..=1)], &options).unwrap_err();
+    assert!(matches!(err, FormatError::ParseTimeout { .. }));
+    let options = FormatOptions::new().with_format_timeout(Some(Duration::ZERO));
+    assert!(matches!(
+        format_range("x=1;\n", &[FormatRange::Lines(1..=1)], &options),
+        Err(FormatError::FormatTimeout { .. })
+    ));
+}
+
+#[test]
+fn test_limits_apply_to_documents() {
+    let options = FormatOptions::new().with_max_input_size(Some(8));
+    assert!(matches!(
+        Document::new("x = 1 + 2;\n", options.clone()),
+        Err(FormatError::InputTooLarge { limit: 8 })
+    ));
+    let mut document = Document::new("x = 1;\n", options).unwrap();
+    assert!(matches!(
+        document.replace(4..5, "1 + 2"),
+        Err(FormatError::InputTooLarge { limit: 8 })
+    ));
+    assert_eq!(document.source(), "x = 1;\n");
+    assert_eq!(document.format().unwrap(), "x = 1;\n");
+    let options = FormatOptions::new().with_format_timeout(Some(Duration::ZERO));
+    let mut document = Document::new("x = 1;\n", options).unwrap();
+    assert!(matches!(
+        document.format(),
+        Err(FormatError::FormatTimeout { .. })
+    ));
+}
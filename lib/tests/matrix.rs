@@ -0,0 +1,88 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for aligning the columns of multi-line matrices.
+
+use matlab_beautifier::{
+    FormatContext, FormatError, FormatOptions, Formatter, FormatterRegistry, Node, NodeFormatter,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Formats `code` with and without a source map, which formats every cell in
+/// place instead of reusing its measured output, and checks both agree.
+fn format(code: &str) -> String {
+    let formatted = Formatter::default().format_str(code).unwrap();
+    let options = FormatOptions::new().with_source_map(true);
+    let mapped = Formatter::new(options).format(code).unwrap();
+    assert_eq!(mapped.text, formatted);
+    formatted
+}
+
+/// Counts the nodes it formats, formatting them as usual.
+struct Count(Arc<AtomicUsize>);
+
+impl NodeFormatter for Count {
+    fn format(&self, context: &mut FormatContext, node: Node) -> Result<(), FormatError> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        context.format_builtin(node)
+    }
+}
+
+/// A matrix nested `depth` times in the first cell of multi-line matrices.
+fn nested(depth: usize) -> String {
+    let mut code = "1".to_string();
+    for _ in 0..depth {
+        code = format!("[{}, -1\n2, 3]", code);
+    }
+    format!("x = {};\n", code)
+}
+
+#[test]
+fn test_columns_are_aligned() {
+    assert_eq!(
+        format("h = [-1 2 3;\n4 -5 6];\n"),
+        "h = [-1  2 3;\n      4 -5 6];\n"
+    );
+}
+
+#[test]
+fn test_cells_with_tabs_are_aligned() {
+    // Formatting the cells in place and reusing their measured output agree.
+    let formatted = format("x = [1 'a\tb' 2\n3 'c' 4];\n");
+    assert_eq!(
+        Formatter::default().format_str(&formatted).unwrap(),
+        formatted
+    );
+}
+
+#[test]
+fn test_nested_matrices_are_aligned() {
+    let formatted = format(&nested(2));
+    assert_eq!(
+        Formatter::default().format_str(&formatted).unwrap(),
+        formatted
+    );
+}
+
+#[test]
+fn test_deeply_nested_matrices_are_measured_once() {
+    // Each matrix is formatted once to measure it and once in place. Formatting
+    // the matrices nested in a cell again for every enclosing matrix would take
+    // depth^2 steps, measuring them again 2^depth steps.
+    let depth = 64;
+    let code = nested(depth);
+    let count = Arc::new(AtomicUsize::new(0));
+    let registry = FormatterRegistry::new().with_formatter("matrix", Count(count.clone()));
+    let formatted = Formatter::default()
+        .with_registry(registry)
+        .format_str(&code)
+        .unwrap();
+    assert_eq!(formatted, format(&code));
+    assert_eq!(formatted.matches('[').count(), depth);
+    let count = count.load(Ordering::Relaxed);
+    assert!(count < 3 * depth, "{} matrices formatted", count);
+}
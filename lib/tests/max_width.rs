@@ -0,0 +1,83 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for wrapping long statements with `...` continuations.
+
+use matlab_beautifier::{beautify, FormatOptions};
+
+fn wrap(code: &str, max_width: usize) -> String {
+    let options = FormatOptions::new().with_max_width(Some(max_width));
+    let formatted = beautify(code, &options).unwrap();
+    assert_eq!(
+        beautify(&formatted, &options).unwrap(),
+        formatted,
+        "wrapping is not idempotent"
+    );
+    formatted
+}
+
+#[test]
+fn test_operator_chain_is_wrapped() {
+    assert_eq!(
+        wrap("x = alpha+beta+gamma+delta;\n", 20),
+        "x = alpha+beta+ ...\n    gamma+delta;\n"
+    );
+}
+
+#[test]
+fn test_lowest_precedence_operator_is_wrapped_first() {
+    assert_eq!(
+        wrap("y = aaaa*bbbb+cccc*dddd;\n", 20),
+        "y = aaaa*bbbb+ ...\n    cccc*dddd;\n"
+    );
+}
+
+#[test]
+fn test_arguments_are_wrapped_after_commas() {
+    assert_eq!(
+        wrap("result = compute(first, second, third);\n", 30),
+        "result = compute(first, ...\n                 second, ...\n                 third);\n"
+    );
+}
+
+#[test]
+fn test_matrix_elements_are_wrapped() {
+    assert_eq!(
+        wrap("m = [1 2 3 4 5 6 7 8];\n", 16),
+        "m = [1 2 3 4 ...\n     5 6 7 8];\n"
+    );
+}
+
+#[test]
+fn test_continuations_are_placed_anew() {
+    let code = "x = 1+ ...\n    2+ ...\n    3;\n";
+    assert_eq!(wrap(code, 100), "x = 1+2+3;\n");
+}
+
+#[test]
+fn test_short_lines_are_left_alone() {
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for name in ["assignment.m", "function_call.m", "matrix_cell.m"] {
+        let code = std::fs::read_to_string(fixtures.join(name)).unwrap();
+        assert_eq!(wrap(&code, 100), code, "{}", name);
+    }
+}
+
+#[test]
+fn test_matrix_rows_follow_wrapped_lines() {
+    assert_eq!(
+        wrap("x = compute(first, second, [1 2\n3 4]);\n", 30),
+        "x = compute(first, second, ...\n            [1 2;\n             3 4]);\n"
+    );
+}
+
+#[test]
+fn test_nested_statements_keep_their_indentation() {
+    assert_eq!(
+        wrap("if x\nresult = compute(first, second, third);\nm = [1 2\n3 4];\nend\n", 30),
+        "if x\n    result = compute(first, ...\n                     second, ...\n                     third);\n    m = [1 2;\n         3 4];\nend\n"
+    );
+}
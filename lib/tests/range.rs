@@ -0,0 +1,58 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for formatting only parts of a file.
+
+use matlab_beautifier::{format_range, FormatOptions, FormatRange};
+
+const CODE: &str = "x=1;\nif a\ny=2;\n  z=3;\nend\nw  =  4\n";
+
+fn format(ranges: &[FormatRange]) -> String {
+    format_range(CODE, ranges, &FormatOptions::default()).unwrap()
+}
+
+#[test]
+fn test_nested_line_is_indented_by_its_block() {
+    assert_eq!(
+        format(&[FormatRange::Lines(3..=3)]),
+        "x=1;\nif a\n    y = 2;\n  z=3;\nend\nw  =  4\n"
+    );
+}
+
+#[test]
+fn test_byte_range_formats_overlapping_statement() {
+    assert_eq!(
+        format(&[FormatRange::Bytes(0..1)]),
+        "x = 1;\nif a\ny=2;\n  z=3;\nend\nw  =  4\n"
+    );
+}
+
+#[test]
+fn test_range_on_header_formats_whole_statement() {
+    assert_eq!(
+        format(&[FormatRange::Lines(2..=2), FormatRange::Lines(6..=6)]),
+        "x=1;\nif a\n    y = 2;\n    z = 3;\nend\nw = 4;\n"
+    );
+}
+
+#[test]
+fn test_empty_ranges_change_nothing() {
+    assert_eq!(format(&[]), CODE);
+}
+
+#[test]
+fn test_statement_further_along_an_under_indented_line() {
+    let code = "if x\nif y\na=1; b=c+d;\nend\nend\n";
+    let options = FormatOptions::default();
+    assert_eq!(
+        format_range(code, &[FormatRange::Lines(3..=3)], &options).unwrap(),
+        "if x\nif y\n        a = 1; b = c+d;\nend\nend\n"
+    );
+    assert_eq!(
+        format_range(code, &[FormatRange::Bytes(15..16)], &options).unwrap(),
+        "if x\nif y\na=1; b = c+d;\nend\nend\n"
+    );
+}
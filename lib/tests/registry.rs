@@ -0,0 +1,103 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for overriding how nodes are formatted.
+
+use matlab_beautifier::{
+    FormatContext, FormatError, FormatOptions, Formatter, FormatterRegistry, Node, NodeFormatter,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Prints numbers with a lowercase exponent.
+struct LowercaseExponent;
+
+impl NodeFormatter for LowercaseExponent {
+    fn format(&self, context: &mut FormatContext, node: Node) -> Result<(), FormatError> {
+        let text = context.text(node)?.to_lowercase();
+        context.print(&text);
+        Ok(())
+    }
+}
+
+/// Keeps the arguments of `addpath` as written, formats other commands as
+/// usual.
+struct VerbatimAddpath;
+
+impl NodeFormatter for VerbatimAddpath {
+    fn format(&self, context: &mut FormatContext, node: Node) -> Result<(), FormatError> {
+        if context.text(node)?.starts_with("addpath") {
+            let text = context.text(node)?;
+            context.print(text);
+            Ok(())
+        } else {
+            context.format_builtin(node)
+        }
+    }
+}
+
+/// Counts the nodes it formats, formatting them as usual.
+struct Count(Arc<AtomicUsize>);
+
+impl NodeFormatter for Count {
+    fn format(&self, context: &mut FormatContext, node: Node) -> Result<(), FormatError> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        context.format_builtin(node)
+    }
+}
+
+fn format(registry: FormatterRegistry, code: &str) -> String {
+    Formatter::new(FormatOptions::default())
+        .with_registry(registry)
+        .format_str(code)
+        .unwrap()
+}
+
+#[test]
+fn test_registered_formatter_is_used() {
+    let registry = FormatterRegistry::new().with_formatter("number", LowercaseExponent);
+    assert_eq!(format(registry, "x=1E3+2;\n"), "x = 1e3+2;\n");
+}
+
+#[test]
+fn test_registered_formatter_can_fall_back_to_builtin() {
+    let registry = FormatterRegistry::new().with_formatter("command", VerbatimAddpath);
+    assert_eq!(
+        format(registry, "addpath  a  b\nhold   on\n"),
+        "addpath  a  b;\nhold on;\n"
+    );
+}
+
+#[test]
+fn test_kind_without_formatter_is_kept_as_is() {
+    let registry = FormatterRegistry::new().without_formatter("matrix");
+    assert_eq!(format(registry, "x=[1,2]\n"), "x = [1,2];\n");
+}
+
+#[test]
+fn test_default_registry_matches_beautify() {
+    let code = "x=[1,2];\nif x\ny=foo(1,2);\nend\n";
+    let options = FormatOptions::default();
+    assert_eq!(
+        format(FormatterRegistry::default(), code),
+        matlab_beautifier::beautify(code, &options).unwrap()
+    );
+}
+
+#[test]
+fn test_nested_nodes_are_formatted_through_the_registry() {
+    let code = "x = [1 % one\n2];\nif x % check\ny = foo(1, 2);\nend\n";
+    let options = FormatOptions::default();
+    for (kind, expected) in [("comment", 2), ("block", 1), ("arguments", 1)] {
+        let count = Arc::new(AtomicUsize::new(0));
+        let registry = FormatterRegistry::new().with_formatter(kind, Count(count.clone()));
+        assert_eq!(
+            format(registry, code),
+            matlab_beautifier::beautify(code, &options).unwrap()
+        );
+        assert_eq!(count.load(Ordering::Relaxed), expected, "{}", kind);
+    }
+}
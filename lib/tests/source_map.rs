@@ -0,0 +1,59 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for mapping input positions to the formatted output.
+
+use matlab_beautifier::{FormatOptions, Formatter, SourceMap};
+
+const CODE: &str = "x=1;\nif a\ny=abc;\nend\n";
+const FORMATTED: &str = "x = 1;\nif a\n    y = abc;\nend\n";
+
+fn source_map() -> SourceMap {
+    let formatter = Formatter::new(FormatOptions::new().with_source_map(true));
+    let formatted = formatter.format(CODE).unwrap();
+    assert_eq!(formatted.text, FORMATTED);
+    formatted.source_map.unwrap()
+}
+
+#[test]
+fn test_source_map_is_optional() {
+    let formatted = Formatter::default().format(CODE).unwrap();
+    assert!(formatted.source_map.is_none());
+}
+
+#[test]
+fn test_position_inside_identifier() {
+    let map = source_map();
+    let input = CODE.find("bc").unwrap();
+    assert_eq!(map.map_position(input), FORMATTED.find("bc").unwrap());
+}
+
+#[test]
+fn test_position_before_indented_statement() {
+    let map = source_map();
+    let input = CODE.find("y=").unwrap();
+    assert_eq!(map.map_position(input), FORMATTED.find("y =").unwrap());
+}
+
+#[test]
+fn test_position_at_end_of_input() {
+    assert_eq!(source_map().map_position(CODE.len()), FORMATTED.len());
+}
+
+#[test]
+fn test_positions_keep_their_order() {
+    let map = source_map();
+    let positions: Vec<usize> = (0..=CODE.len()).map(|i| map.map_position(i)).collect();
+    assert!(positions.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(
+        positions[CODE.find("if").unwrap()],
+        FORMATTED.find("if").unwrap()
+    );
+    assert_eq!(
+        positions[CODE.find("end").unwrap()],
+        FORMATTED.find("end").unwrap()
+    );
+}
@@ -0,0 +1,71 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for checking that formatting does not change what code means.
+
+use matlab_beautifier::{beautify, verify_equivalent, FormatError, FormatOptions};
+
+#[test]
+fn test_fixtures_pass_verification() {
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let options = FormatOptions::new().with_verify(true);
+    for entry in std::fs::read_dir(fixtures).unwrap() {
+        let path = entry.unwrap().path();
+        let code = std::fs::read_to_string(&path).unwrap();
+        if let Err(err) = beautify(&code, &options) {
+            panic!("{}: {}", path.display(), err);
+        }
+    }
+}
+
+#[test]
+fn test_normalized_punctuation_is_equivalent() {
+    let original = "x=1, y=[1,2;3,4]\nif x, y=2, end\n";
+    let formatted = "x = 1, y = [1 2; 3 4];\nif x\n    y = 2;\nend\n";
+    verify_equivalent(original, formatted).unwrap();
+}
+
+#[test]
+fn test_comments_may_move_and_continuations_are_ignored() {
+    let original = "x = 1+ ... add\n    2; %two\n";
+    let formatted = "x = 1 + ... add\n    2;\n% two\n";
+    verify_equivalent(original, formatted).unwrap();
+    verify_equivalent("x = 1+ ...\n    2;\n", "x = 1+2;\n").unwrap();
+}
+
+#[test]
+fn test_dropped_comment_is_reported() {
+    let original = "x = 1+ ... add\n    2; % two\n";
+    let err = verify_equivalent(original, "% two\nx = 1+2;\n").unwrap_err();
+    assert!(
+        matches!(
+            err,
+            FormatError::NotEquivalent {
+                kind: "line_continuation",
+                ..
+            }
+        ),
+        "expected a verification error, got {:?}",
+        err
+    );
+}
+
+#[test]
+fn test_changed_meaning_is_reported() {
+    let err = verify_equivalent("x = [a -b];\n", "x = [a - b];\n").unwrap_err();
+    assert!(
+        matches!(err, FormatError::NotEquivalent { .. }),
+        "expected a verification error, got {:?}",
+        err
+    );
+    assert_eq!(err.span().unwrap().start.line, 1);
+}
+
+#[test]
+fn test_changed_operator_is_reported() {
+    let err = verify_equivalent("x = a+b;\n", "x = a-b;\n").unwrap_err();
+    assert!(matches!(err, FormatError::NotEquivalent { kind: "+", .. }));
+}
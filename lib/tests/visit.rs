@@ -0,0 +1,153 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Tests for walking syntax trees.
+
+use matlab_beautifier::ast::{
+    parse, AstNode, ClassDefinition, Comment, FunctionDefinition, IfStatement, Methods, Property,
+};
+use matlab_beautifier::{walk, Statement, Visitor};
+
+/// Writes down the hooks called, with the text of what they were called on.
+struct Recorder<'t> {
+    code: &'t str,
+    events: Vec<String>,
+}
+
+impl<'t> Recorder<'t> {
+    fn text(&self, node: matlab_beautifier::Node) -> &'t str {
+        node.utf8_text(self.code.as_bytes()).unwrap()
+    }
+}
+
+impl<'t> Visitor<'t> for Recorder<'t> {
+    fn enter_statement(&mut self, statement: &Statement<'t>) {
+        for comment in statement.leading_comments() {
+            let event = format!("leading {}", self.text(comment.syntax()));
+            self.events.push(event);
+        }
+        let first_line = self.text(statement.node()).lines().next().unwrap();
+        self.events.push(format!("statement {}", first_line));
+        if let Some(comment) = statement.trailing_comment() {
+            let event = format!("trailing {}", self.text(comment.syntax()));
+            self.events.push(event);
+        }
+        for comment in statement.directives() {
+            let event = format!("directive {}", self.text(comment.syntax()));
+            self.events.push(event);
+        }
+    }
+
+    fn visit_dangling_comment(&mut self, comment: Comment<'t>) {
+        let event = format!("dangling {}", self.text(comment.syntax()));
+        self.events.push(event);
+    }
+
+    fn enter_if(&mut self, _statement: IfStatement<'t>) {
+        self.events.push("enter if".to_string());
+    }
+
+    fn leave_if(&mut self, _statement: IfStatement<'t>) {
+        self.events.push("leave if".to_string());
+    }
+
+    fn enter_function(&mut self, function: FunctionDefinition<'t>) {
+        let event = format!("enter function {}", self.text(function.name().unwrap()));
+        self.events.push(event);
+    }
+
+    fn leave_function(&mut self, _function: FunctionDefinition<'t>) {
+        self.events.push("leave function".to_string());
+    }
+
+    fn enter_class(&mut self, class: ClassDefinition<'t>) {
+        let event = format!("enter class {}", self.text(class.name().unwrap()));
+        self.events.push(event);
+    }
+
+    fn enter_methods(&mut self, _methods: Methods<'t>) {
+        self.events.push("enter methods".to_string());
+    }
+
+    fn visit_property(&mut self, property: Property<'t>) {
+        let event = format!("property {}", self.text(property.name().unwrap()));
+        self.events.push(event);
+    }
+}
+
+fn record(code: &str) -> Vec<String> {
+    let tree = parse(code).unwrap();
+    let mut recorder = Recorder {
+        code,
+        events: vec![],
+    };
+    walk(&tree, code, &mut recorder);
+    recorder.events
+}
+
+#[test]
+fn test_comments_are_attached_to_statements() {
+    let code = "% first\n% second\nx = 1; % one\n\ny = 2;\n% last\n";
+    assert_eq!(
+        record(code),
+        [
+            "leading % first",
+            "leading % second",
+            "statement x = 1",
+            "trailing % one",
+            "statement y = 2",
+            "dangling % last",
+        ]
+    );
+}
+
+#[test]
+fn test_compound_statements_are_entered_and_left() {
+    let code = "if x %#ok\n    % inside\n    y = 1;\nelse\n    % nothing\nend\n";
+    assert_eq!(
+        record(code),
+        [
+            "statement if x %#ok",
+            "directive %#ok",
+            "enter if",
+            "leading % inside",
+            "statement y = 1",
+            "dangling % nothing",
+            "leave if",
+        ]
+    );
+}
+
+#[test]
+fn test_functions_and_arguments_are_walked() {
+    let code = "function f(x)\n    arguments\n        x double\n    end\n    disp(x);\nend\n";
+    assert_eq!(
+        record(code),
+        [
+            "statement function f(x)",
+            "enter function f",
+            "property x",
+            "statement disp(x)",
+            "leave function",
+        ]
+    );
+}
+
+#[test]
+fn test_class_members_are_walked_in_order() {
+    let code = "classdef Foo\n    methods\n        function obj = Foo()\n        end\n    end\n    properties\n        a\n    end\nend\n";
+    assert_eq!(
+        record(code),
+        [
+            "statement classdef Foo",
+            "enter class Foo",
+            "enter methods",
+            "enter function Foo",
+            "leave function",
+            "property a",
+        ]
+    );
+}
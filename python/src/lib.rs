@@ -0,0 +1,173 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! The `matlab_beautifier` Python module.
+//!
+//! ```python
+//! import matlab_beautifier
+//!
+//! matlab_beautifier.format("x=1+2\n", sparse_add=True)  # "x = 1 + 2;\n"
+//! ```
+
+use beautifier::{FormatOptions, Formatter, Span};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+create_exception!(
+    matlab_beautifier,
+    FormatError,
+    PyException,
+    "Formatting failed. `line` and `column` are 1-based and None when the \
+     error is not about a place in the code, `path` is the file being \
+     formatted, if any."
+);
+
+/// Builds the options from the keyword arguments of `format` and
+/// `format_files`, which are named like the `with_*` methods of
+/// `FormatOptions`.
+fn options(kwargs: Option<&Bound<PyDict>>) -> PyResult<FormatOptions> {
+    let mut options = FormatOptions::new();
+    let Some(kwargs) = kwargs else {
+        return Ok(options);
+    };
+    for (key, value) in kwargs {
+        let key: String = key.extract()?;
+        options = match key.as_str() {
+            "sparse_math" => options.with_sparse_math(value.extract()?),
+            "sparse_add" => options.with_sparse_add(value.extract()?),
+            "tolerant" => options.with_tolerant(value.extract()?),
+            "max_width" => options.with_max_width(value.extract()?),
+            "verify" => options.with_verify(value.extract()?),
+            "ensure_idempotent" => options.with_ensure_idempotent(value.extract()?),
+            "max_depth" => options.with_max_depth(value.extract()?),
+            "max_input_size" => options.with_max_input_size(value.extract()?),
+            "parse_timeout" => options.with_parse_timeout(seconds(value.extract()?)?),
+            "format_timeout" => options.with_format_timeout(seconds(value.extract()?)?),
+            _ => return Err(PyTypeError::new_err(format!("unexpected option '{}'", key))),
+        };
+    }
+    Ok(options)
+}
+
+/// Converts a timeout in seconds, if any.
+fn seconds(seconds: Option<f64>) -> PyResult<Option<Duration>> {
+    seconds
+        .map(|seconds| {
+            Duration::try_from_secs_f64(seconds)
+                .map_err(|err| PyValueError::new_err(err.to_string()))
+        })
+        .transpose()
+}
+
+/// Converts an error of the formatter into a `FormatError` exception.
+fn format_error(py: Python, err: beautifier::FormatError, path: Option<PathBuf>) -> PyErr {
+    exception(py, &err.to_string(), err.span(), path)
+}
+
+/// A `FormatError` exception with `message`, prefixed by `path` if any.
+fn exception(py: Python, message: &str, span: Option<Span>, path: Option<PathBuf>) -> PyErr {
+    let message = match &path {
+        Some(path) => format!("{}: {}", path.display(), message),
+        None => message.to_string(),
+    };
+    let exception = FormatError::new_err(message);
+    let value = exception.value(py);
+    let set_attributes = || {
+        value.setattr("line", span.map(|span| span.start.line))?;
+        value.setattr("column", span.map(|span| span.start.column))?;
+        value.setattr("path", path)
+    };
+    match set_attributes() {
+        Ok(()) => exception,
+        Err(err) => err,
+    }
+}
+
+/// Formats `code` and returns the beautified source.
+///
+/// The options are keyword arguments: `sparse_math`, `sparse_add`,
+/// `tolerant`, `verify` and `ensure_idempotent` are booleans, `max_width`
+/// is the column to wrap statements at, or None, and `max_depth` how deeply
+/// the code may be nested. `max_input_size` limits the code to a number of
+/// bytes, `parse_timeout` and `format_timeout` the time spent on it to a
+/// number of seconds, each None for no limit.
+#[pyfunction]
+#[pyo3(signature = (code, **options))]
+fn format(py: Python, code: &str, options: Option<&Bound<PyDict>>) -> PyResult<String> {
+    let formatter = Formatter::new(self::options(options)?);
+    formatter
+        .format_str(code)
+        .map_err(|err| format_error(py, err, None))
+}
+
+/// Formats the files at `paths`, detecting their encodings, and returns the
+/// beautified sources in the same order. The files are not changed.
+///
+/// The files are formatted on all cores without holding the GIL. Raises a
+/// `FormatError` for the first file that fails, in the order of `paths`, also
+/// when the formatter panicked on it. The options are those of `format`.
+#[pyfunction]
+#[pyo3(signature = (paths, **options))]
+fn format_files(
+    py: Python,
+    paths: Vec<PathBuf>,
+    options: Option<&Bound<PyDict>>,
+) -> PyResult<Vec<String>> {
+    let formatter = Formatter::new(self::options(options)?);
+    let results = py.detach(|| format_in_parallel(&formatter, &paths));
+    paths
+        .into_iter()
+        .zip(results)
+        .map(|(path, result)| match result {
+            Some(result) => result.map_err(|err| format_error(py, err, Some(path))),
+            None => Err(exception(py, "the formatter panicked", None, Some(path))),
+        })
+        .collect()
+}
+
+/// Formats `paths` on all cores. The result of a file is None if formatting
+/// it panicked, which is a bug in the formatter.
+fn format_in_parallel(
+    formatter: &Formatter,
+    paths: &[PathBuf],
+) -> Vec<Option<Result<String, beautifier::FormatError>>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = paths.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let handle = scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|path| {
+                            catch_unwind(AssertUnwindSafe(|| formatter.format_file(path))).ok()
+                        })
+                        .collect::<Vec<_>>()
+                });
+                (chunk.len(), handle)
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|(len, handle)| handle.join().unwrap_or_else(|_| (0..len).map(|_| None).collect()))
+            .collect()
+    })
+}
+
+#[pymodule]
+pub fn matlab_beautifier(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add("FormatError", module.py().get_type::<FormatError>())?;
+    module.add_function(wrap_pyfunction!(format, module)?)?;
+    module.add_function(wrap_pyfunction!(format_files, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,44 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Runs tests/test_format.py with the module registered in the local Python
+//! interpreter.
+
+use matlab_beautifier_python::matlab_beautifier;
+use pyo3::ffi::c_str;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+#[test]
+fn test_python_module() {
+    pyo3::append_to_inittab!(matlab_beautifier);
+    Python::initialize();
+    Python::attach(|py| {
+        let locals = PyDict::new(py);
+        locals
+            .set_item("tests", concat!(env!("CARGO_MANIFEST_DIR"), "/tests"))
+            .unwrap();
+        py.run(
+            c_str!(
+                "import sys, unittest\n\
+                 sys.path.insert(0, tests)\n\
+                 suite = unittest.defaultTestLoader.loadTestsFromName('test_format')\n\
+                 result = unittest.TextTestRunner(verbosity=2).run(suite)\n\
+                 passed = result.wasSuccessful()\n"
+            ),
+            None,
+            Some(&locals),
+        )
+        .unwrap();
+        let passed: bool = locals
+            .get_item("passed")
+            .unwrap()
+            .unwrap()
+            .extract()
+            .unwrap();
+        assert!(passed, "tests/test_format.py failed");
+    });
+}
@@ -0,0 +1,115 @@
+# This Source Code Form is subject to the terms of the Mozilla Public
+# License, v. 2.0. If a copy of the MPL was not distributed with this
+# file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+"""Tests for the Python module, run by tests/python.rs or with
+`python -m unittest` against an installed wheel."""
+
+import os
+import tempfile
+import threading
+import time
+import unittest
+
+import matlab_beautifier
+
+
+class FormatTest(unittest.TestCase):
+    def test_format(self):
+        self.assertEqual(matlab_beautifier.format("x=1+2\n"), "x = 1+2;\n")
+
+    def test_options(self):
+        formatted = matlab_beautifier.format("x=1+2*3\n", sparse_math=True)
+        self.assertEqual(formatted, "x = 1 + 2 * 3;\n")
+        formatted = matlab_beautifier.format("x=1+2*3\n", sparse_add=True)
+        self.assertEqual(formatted, "x = 1 + 2*3;\n")
+
+    def test_unknown_option(self):
+        with self.assertRaises(TypeError):
+            matlab_beautifier.format("x=1\n", sparse=True)
+
+    def test_format_error_has_position(self):
+        with self.assertRaises(matlab_beautifier.FormatError) as context:
+            matlab_beautifier.format("x = 1;\ny = (2 +\n")
+        self.assertGreaterEqual(context.exception.line, 1)
+        self.assertGreaterEqual(context.exception.column, 1)
+        self.assertIsNone(context.exception.path)
+
+
+class FormatFilesTest(unittest.TestCase):
+    def setUp(self):
+        self.directory = tempfile.TemporaryDirectory()
+
+    def tearDown(self):
+        self.directory.cleanup()
+
+    def write(self, name, code):
+        path = os.path.join(self.directory.name, name)
+        with open(path, "w", encoding="utf-8") as file:
+            file.write(code)
+        return path
+
+    def test_format_files(self):
+        paths = [self.write(f"f{i}.m", f"x{i}=[1,2]\n") for i in range(20)]
+        formatted = matlab_beautifier.format_files(paths, sparse_add=True)
+        self.assertEqual(formatted, [f"x{i} = [1 2];\n" for i in range(20)])
+        with open(paths[0], encoding="utf-8") as file:
+            self.assertEqual(file.read(), "x0=[1,2]\n")
+
+    def test_first_failing_file_is_reported(self):
+        good = self.write("good.m", "x=1\n")
+        bad = self.write("bad.m", "x = (1 +\n")
+        with self.assertRaises(matlab_beautifier.FormatError) as context:
+            matlab_beautifier.format_files([good, bad])
+        self.assertEqual(context.exception.path, bad)
+        self.assertIsNotNone(context.exception.line)
+
+    def test_missing_file_is_reported(self):
+        missing = os.path.join(self.directory.name, "missing.m")
+        with self.assertRaises(matlab_beautifier.FormatError) as context:
+            matlab_beautifier.format_files([missing])
+        self.assertIsNone(context.exception.line)
+
+    def ticks_while_formatting(self, paths):
+        """How often another thread ticks while format_files runs."""
+        state = {"running": False}
+        ticks = []
+        done = threading.Event()
+
+        class LastPath:
+            """Marks format_files as running once it has read all paths."""
+
+            def __fspath__(self):
+                state["running"] = True
+                return paths[-1]
+
+        def tick():
+            while not done.is_set():
+                if state["running"]:
+                    ticks.append(1)
+                done.wait(0.0001)
+
+        thread = threading.Thread(target=tick)
+        thread.start()
+        try:
+            matlab_beautifier.format_files(paths[:-1] + [LastPath()])
+            state["running"] = False
+        finally:
+            done.set()
+            thread.join()
+        return len(ticks)
+
+    def test_gil_is_released(self):
+        # Without releasing the GIL, the thread could tick once at most,
+        # between format_files returning and the flag being cleared. The files
+        # are formatted more often until that takes long enough to tick twice.
+        paths = [self.write(f"f{i}.m", "x = [1, 2; 3, 4];\n" * 200) for i in range(50)]
+        deadline = time.monotonic() + 30
+        ticks = self.ticks_while_formatting(paths)
+        while ticks <= 1 and time.monotonic() < deadline:
+            paths = paths * 2
+            ticks = self.ticks_while_formatting(paths)
+        self.assertGreater(ticks, 1)
+
+if __name__ == "__main__":
+    unittest.main()